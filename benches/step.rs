@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use rusty_chip::{asm, Chip8};
+
+const STEPS: u64 = 1_000_000;
+
+// Arithmetic, a skip and jumps, nothing that touches the screen
+const TIGHT_LOOP: &str = "
+    loop:
+        ADD V0, 1
+        LD V1, V0
+        XOR V1, V2
+        SE V0, 0
+        JP loop
+        ADD V2, 1
+        JP loop";
+
+const SCREEN_ROM: &[u8] = include_bytes!("../tests/roms/screen.ch8");
+
+fn machine_with_rom(rom: &[u8]) -> Chip8
+{
+    let mut machine = Chip8::new_seeded(0);
+    machine.load_bytes(rom).unwrap();
+    machine
+}
+
+fn step(c: &mut Criterion)
+{
+    let tight_loop = asm::assemble(TIGHT_LOOP).unwrap();
+
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(STEPS));
+
+    group.bench_function("tight_loop", |b| b.iter(||
+    {
+        let mut machine = machine_with_rom(&tight_loop);
+        for _i in 0..STEPS
+        {
+            machine.step().unwrap();
+        }
+        black_box(machine.register(0x2))
+    }));
+
+    // Mostly draws and then the final self jump
+    group.bench_function("screen_rom", |b| b.iter(||
+    {
+        let mut machine = machine_with_rom(SCREEN_ROM);
+        machine.run_for(STEPS as usize).unwrap();
+        black_box(machine.pixel(0, 0))
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
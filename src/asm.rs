@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use crate::error::AsmError;
+use crate::machine::ROM_MEMORY_START;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand
+{
+    Register(u16),
+    Value(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Rpl,
+}
+
+struct Statement<'a>
+{
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+// Two passes, the first records label addresses so that
+// instructions can jump forward to labels defined later on
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError>
+{
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = ROM_MEMORY_START;
+
+    for (i, raw) in source.lines().enumerate()
+    {
+        let line = i + 1;
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+
+        if let Some(colon) = text.find(':')
+        {
+            let label = text[.. colon].trim();
+
+            if !is_identifier(label)
+            {
+                return Err(AsmError::new(line, format!("Invalid Label: {}", label)));
+            }
+
+            if labels.insert(label.to_string(), address).is_some()
+            {
+                return Err(AsmError::new(line, format!("Duplicate Label: {}", label)));
+            }
+
+            text = text[colon + 1 ..].trim();
+        }
+
+        if text.is_empty()
+        {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.find(char::is_whitespace)
+        {
+            Some(split) => (&text[.. split], text[split ..].trim()),
+            None => (text, ""),
+        };
+
+        let operands: Vec<&str> = if rest.is_empty()
+        {
+            Vec::new()
+        }
+        else
+        {
+            rest.split(',').map(str::trim).collect()
+        };
+
+        let mnemonic = mnemonic.to_uppercase();
+
+        address += match mnemonic.as_str()
+        {
+            "DB" => operands.len() as u16,
+            "DW" => 2 * operands.len() as u16,
+            _ => 2,
+        };
+
+        statements.push(Statement { line, mnemonic, operands });
+    }
+
+    let mut bytes = Vec::new();
+    for statement in &statements
+    {
+        encode(statement, &labels, &mut bytes).map_err(|message| AsmError::new(statement.line, message))?;
+    }
+
+    Ok(bytes)
+}
+
+fn encode(statement: &Statement, labels: &HashMap<String, u16>, bytes: &mut Vec<u8>) -> Result<(), String>
+{
+    use Operand::*;
+
+    let operands = statement.operands.iter()
+                            .map(|operand| parse_operand(operand, labels))
+                            .collect::<Result<Vec<Operand>, String>>()?;
+
+    match statement.mnemonic.as_str()
+    {
+        "DB" =>
+        {
+            for operand in operands
+            {
+                match operand
+                {
+                    Value(v) => bytes.push(byte(v)? as u8),
+                    _ => return Err(String::from("DB Expects Values")),
+                }
+            }
+            return Ok(());
+        },
+
+        "DW" =>
+        {
+            for operand in operands
+            {
+                match operand
+                {
+                    Value(v) => bytes.extend_from_slice(&[(v >> 8) as u8, v as u8]),
+                    _ => return Err(String::from("DW Expects Values")),
+                }
+            }
+            return Ok(());
+        },
+
+        _ => (),
+    }
+
+    let opcode = match (statement.mnemonic.as_str(), operands.as_slice())
+    {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("EXIT", []) => 0x00FD,
+        ("SYS", [Value(nnn)]) => address(*nnn)?,
+
+        ("JP", [Value(nnn)]) => 0x1000 | address(*nnn)?,
+        ("JP", [Register(0), Value(nnn)]) => 0xB000 | address(*nnn)?,
+        ("CALL", [Value(nnn)]) => 0x2000 | address(*nnn)?,
+
+        ("SE", [Register(x), Value(kk)]) => 0x3000 | x << 8 | byte(*kk)?,
+        ("SE", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("SNE", [Register(x), Value(kk)]) => 0x4000 | x << 8 | byte(*kk)?,
+        ("SNE", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+
+        ("LD", [Register(x), Value(kk)]) => 0x6000 | x << 8 | byte(*kk)?,
+        ("LD", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("LD", [I, Value(nnn)]) => 0xA000 | address(*nnn)?,
+        ("LD", [Register(x), DelayTimer]) => 0xF007 | x << 8,
+        ("LD", [Register(x), Key]) => 0xF00A | x << 8,
+        ("LD", [DelayTimer, Register(x)]) => 0xF015 | x << 8,
+        ("LD", [SoundTimer, Register(x)]) => 0xF018 | x << 8,
+        ("LD", [Font, Register(x)]) => 0xF029 | x << 8,
+        ("LD", [BigFont, Register(x)]) => 0xF030 | x << 8,
+        ("LD", [Bcd, Register(x)]) => 0xF033 | x << 8,
+        ("LD", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [Rpl, Register(x)]) => 0xF075 | x << 8,
+        ("LD", [Register(x), Rpl]) => 0xF085 | x << 8,
+
+        ("ADD", [Register(x), Value(kk)]) => 0x7000 | x << 8 | byte(*kk)?,
+        ("ADD", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("ADD", [I, Register(x)]) => 0xF01E | x << 8,
+
+        ("OR", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("SUB", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+
+        ("RND", [Register(x), Value(kk)]) => 0xC000 | x << 8 | byte(*kk)?,
+        ("DRW", [Register(x), Register(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | nibble(*n)?,
+
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+
+        _ => return Err(format!("Invalid Instruction: {} {}", statement.mnemonic, statement.operands.join(", "))),
+    };
+
+    bytes.extend_from_slice(&[(opcode >> 8) as u8, opcode as u8]);
+
+    Ok(())
+}
+
+fn parse_operand(text: &str, labels: &HashMap<String, u16>) -> Result<Operand, String>
+{
+    let upper = text.to_uppercase();
+
+    let operand = match upper.as_str()
+    {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        "R" => Operand::Rpl,
+
+        _ if upper.len() == 2 && upper.starts_with('V') =>
+        {
+            let x = u16::from_str_radix(&upper[1 ..], 16).map_err(|_| format!("Invalid Register: {}", text))?;
+            Operand::Register(x)
+        },
+
+        _ if upper.starts_with("0X") => Operand::Value(parse_number(&upper[2 ..], 16, text)?),
+
+        _ if upper.starts_with("0B") => Operand::Value(parse_number(&upper[2 ..], 2, text)?),
+
+        _ if upper.starts_with(|c: char| c.is_ascii_digit()) => Operand::Value(parse_number(&upper, 10, text)?),
+
+        _ => match labels.get(text)
+        {
+            Some(&address) => Operand::Value(address),
+            None => return Err(format!("Unknown Label: {}", text)),
+        },
+    };
+
+    Ok(operand)
+}
+
+fn parse_number(digits: &str, radix: u32, text: &str) -> Result<u16, String>
+{
+    u16::from_str_radix(digits, radix).map_err(|_| format!("Invalid Number: {}", text))
+}
+
+fn is_identifier(text: &str) -> bool
+{
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn address(value: u16) -> Result<u16, String>
+{
+    limit(value, 0xFFF)
+}
+
+fn byte(value: u16) -> Result<u16, String>
+{
+    limit(value, 0xFF)
+}
+
+fn nibble(value: u16) -> Result<u16, String>
+{
+    limit(value, 0xF)
+}
+
+fn limit(value: u16, max: u16) -> Result<u16, String>
+{
+    if value > max
+    {
+        return Err(format!("Value Out Of Range: 0x{:X} > 0x{:X}", value, max));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::disasm;
+
+    #[test]
+    fn round_trips_through_disassembler()
+    {
+        let source = "
+            start:
+                CLS
+                LD V2, 0x0A         ; comment
+                LD I, sprite
+                DRW V0, V1, 5
+                SHL VA, VB
+                LD [I], V3
+                JP start
+            sprite:
+                DW 0xF090";
+
+        let rom = assemble(source).unwrap();
+        let text: Vec<String> = disasm::disassemble_range(&rom, 0, rom.len(), None)
+                                    .into_iter()
+                                    .map(|(_, text)| text)
+                                    .collect();
+
+        assert_eq!(text, ["CLS", "LD V2, 0x0A", "LD I, 0x20E", "DRW V0, V1, 5",
+                          "SHL VA, VB", "LD [I], V3", "JP 0x200", "DW 0xF090"]);
+    }
+
+    #[test]
+    fn errors_report_line_numbers()
+    {
+        let error = assemble("CLS\nLD V0, 0x100").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = assemble("CLS\n\nJP nowhere").unwrap_err();
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn db_emits_raw_bytes()
+    {
+        assert_eq!(assemble("db 0xF0, 0x90, 144").unwrap(), vec![0xF0, 0x90, 0x90]);
+    }
+}
@@ -0,0 +1,167 @@
+use ggez::graphics::Color;
+use ggez::input::keyboard::KeyCode;
+
+use crate::emulator::{Emulator, TIMER_HZ};
+use crate::machine::{Chip8, NUM_KEYS};
+use crate::quirks::Platform;
+use crate::tone::Waveform;
+
+const DEFAULT_SCALE: f32 = 10.0;
+
+// Everything left unset keeps the Emulator's default, eg -
+//
+// let emulator = EmulatorBuilder::new()
+//                    .scale(12.0)
+//                    .cycles_per_frame(15)
+//                    .platform(Platform::SuperChip)
+//                    .build();
+pub struct EmulatorBuilder
+{
+    machine: Option<Chip8>,
+    scale: f32,
+    clock_hz: Option<u32>,
+    colors: Option<(Color, Color)>,
+    controls: Option<[KeyCode; NUM_KEYS]>,
+    platform: Option<Platform>,
+    beep: Option<(f32, Waveform)>,
+    volume: Option<f32>,
+}
+
+impl EmulatorBuilder
+{
+    pub fn new() -> EmulatorBuilder
+    {
+        EmulatorBuilder
+        {
+            machine: None,
+            scale: DEFAULT_SCALE,
+            clock_hz: None,
+            colors: None,
+            controls: None,
+            platform: None,
+            beep: None,
+            volume: None,
+        }
+    }
+
+    // A machine set up beforehand, eg - seeded or with breakpoints
+    pub fn machine(mut self, machine: Chip8) -> EmulatorBuilder
+    {
+        self.machine = Some(machine);
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> EmulatorBuilder
+    {
+        self.scale = scale;
+        self
+    }
+
+    pub fn cycles_per_frame(self, cycles: u32) -> EmulatorBuilder
+    {
+        self.clock_hz(cycles * TIMER_HZ)
+    }
+
+    pub fn clock_hz(mut self, hz: u32) -> EmulatorBuilder
+    {
+        self.clock_hz = Some(hz);
+        self
+    }
+
+    pub fn colors(mut self, foreground: Color, background: Color) -> EmulatorBuilder
+    {
+        self.colors = Some((foreground, background));
+        self
+    }
+
+    pub fn controls(mut self, controls: [KeyCode; NUM_KEYS]) -> EmulatorBuilder
+    {
+        self.controls = Some(controls);
+        self
+    }
+
+    // Also stops known ROMs from picking their own quirks
+    pub fn platform(mut self, platform: Platform) -> EmulatorBuilder
+    {
+        self.platform = Some(platform);
+        self
+    }
+
+    pub fn beep(mut self, freq_hz: f32, waveform: Waveform) -> EmulatorBuilder
+    {
+        self.beep = Some((freq_hz, waveform));
+        self
+    }
+
+    pub fn volume(mut self, volume: f32) -> EmulatorBuilder
+    {
+        self.volume = Some(volume);
+        self
+    }
+
+    pub fn build(self) -> Emulator
+    {
+        let mut machine = self.machine.unwrap_or_else(Chip8::new);
+        if let Some(platform) = self.platform
+        {
+            machine.set_quirks(platform.quirks());
+        }
+
+        let mut e = Emulator::new(machine, self.scale);
+        e.set_auto_quirks(self.platform.is_none());
+
+        if let Some(hz) = self.clock_hz
+        {
+            e.set_clock_hz(hz);
+        }
+        if let Some((foreground, background)) = self.colors
+        {
+            let mut palette = e.palette();
+            palette[0] = background;
+            palette[1] = foreground;
+            e.set_palette(palette);
+        }
+        if let Some(controls) = self.controls
+        {
+            e.set_controls(controls);
+        }
+        if let Some((freq_hz, waveform)) = self.beep
+        {
+            e.set_beep(freq_hz, waveform);
+        }
+        if let Some(volume) = self.volume
+        {
+            e.set_volume(volume);
+        }
+
+        e
+    }
+}
+
+impl Default for EmulatorBuilder
+{
+    fn default() -> Self
+    {
+        EmulatorBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn colors_replace_the_first_two_palette_entries()
+    {
+        let default = EmulatorBuilder::new().build().palette();
+
+        let foreground = Color::new(0.2, 1.0, 0.4, 1.0);
+        let background = Color::new(0.1, 0.1, 0.1, 1.0);
+        let palette = EmulatorBuilder::new().colors(foreground, background).build().palette();
+
+        assert_eq!(palette[0], background);
+        assert_eq!(palette[1], foreground);
+        assert_eq!(palette[2 ..], default[2 ..]);
+    }
+}
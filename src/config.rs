@@ -0,0 +1,274 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ggez::graphics::Color;
+use ggez::input::keyboard::KeyCode;
+use toml::Value;
+
+use crate::keymap;
+use crate::machine::NUM_KEYS;
+use crate::quirks::{IndexIncrement, Platform, Quirks};
+
+// Every field is optional, anything left out keeps its default, eg -
+//
+// scale = 12
+// cycles_per_frame = 10
+// controls = "x 1 2 3 q w e a s d z c 4 r f v"
+// foreground = "#33FF66"
+// background = "#101010"
+// platform = "schip"
+// flags_dir = "saves/flags"
+//
+// [quirks]
+// wrap_sprites = true
+// jump_uses_vx = false
+// index_increment_on_store = "x_plus_one"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config
+{
+    pub scale: Option<f32>,
+    pub cycles_per_frame: Option<u32>,
+    pub controls: Option<[KeyCode; NUM_KEYS]>,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub platform: Option<Platform>,
+    pub flags_dir: Option<PathBuf>,
+
+    pub wrap_sprites: Option<bool>,
+    pub reset_vf_on_logic: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub jump_uses_vx: Option<bool>,
+    pub index_increment_on_store: Option<IndexIncrement>,
+}
+
+impl Config
+{
+    pub fn load(path: &Path) -> Result<Config, String>
+    {
+        let text = fs::read_to_string(path).map_err(|e| format!("Error Reading Config {}: {}", path.display(), e))?;
+        Config::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String>
+    {
+        let root: Value = text.parse().map_err(|e| format!("Error Parsing Config: {}", e))?;
+
+        let quirks = root.get("quirks");
+        if quirks.map_or(false, |quirks| quirks.as_table().is_none())
+        {
+            return Err(String::from("Invalid Value For quirks"));
+        }
+
+        let controls = match string(&root, "controls")?
+        {
+            Some(mapping) => Some(keymap::parse_controls(mapping)?),
+            None => None,
+        };
+
+        let platform = match string(&root, "platform")?
+        {
+            Some(name) => Some(Platform::from_name(name).ok_or_else(|| format!("Unknown Platform: {}", name))?),
+            None => None,
+        };
+
+        // "x_plus_one" like the VIP, "x" like CHIP-48 or "unchanged" like SCHIP
+        let index_increment_on_store = match quirks.map_or(Ok(None), |quirks| string(quirks, "index_increment_on_store"))?
+        {
+            Some(name) => Some(IndexIncrement::from_name(name).ok_or_else(|| invalid("index_increment_on_store"))?),
+            None => None,
+        };
+
+        Ok(Config
+        {
+            scale: float(&root, "scale")?,
+            cycles_per_frame: integer(&root, "cycles_per_frame")?,
+            controls,
+            foreground: color(&root, "foreground")?,
+            background: color(&root, "background")?,
+            platform,
+            flags_dir: string(&root, "flags_dir")?.map(PathBuf::from),
+
+            wrap_sprites: quirks.map_or(Ok(None), |quirks| boolean(quirks, "wrap_sprites"))?,
+            reset_vf_on_logic: quirks.map_or(Ok(None), |quirks| boolean(quirks, "reset_vf_on_logic"))?,
+            display_wait: quirks.map_or(Ok(None), |quirks| boolean(quirks, "display_wait"))?,
+            jump_uses_vx: quirks.map_or(Ok(None), |quirks| boolean(quirks, "jump_uses_vx"))?,
+            index_increment_on_store,
+        })
+    }
+
+    // $XDG_CONFIG_HOME/rusty-chip/config.toml, falling back to ~/.config
+    pub fn default_path() -> Option<PathBuf>
+    {
+        let base = match env::var_os("XDG_CONFIG_HOME")
+        {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(base.join("rusty-chip").join("config.toml"))
+    }
+
+    pub fn sets_quirks(&self) -> bool
+    {
+        self.platform.is_some()
+            || self.wrap_sprites.is_some()
+            || self.reset_vf_on_logic.is_some()
+            || self.display_wait.is_some()
+            || self.jump_uses_vx.is_some()
+            || self.index_increment_on_store.is_some()
+    }
+
+    // The platform is picked first and the single quirks override it
+    pub fn quirks(&self, platform: Option<Platform>) -> Quirks
+    {
+        let mut quirks = platform.or(self.platform).map_or_else(Quirks::default, Platform::quirks);
+
+        if let Some(wrap_sprites) = self.wrap_sprites
+        {
+            quirks.wrap_sprites = wrap_sprites;
+        }
+        if let Some(reset_vf_on_logic) = self.reset_vf_on_logic
+        {
+            quirks.reset_vf_on_logic = reset_vf_on_logic;
+        }
+        if let Some(display_wait) = self.display_wait
+        {
+            quirks.display_wait = display_wait;
+        }
+        if let Some(jump_uses_vx) = self.jump_uses_vx
+        {
+            quirks.jump_uses_vx = jump_uses_vx;
+        }
+        if let Some(index_increment_on_store) = self.index_increment_on_store
+        {
+            quirks.index_increment_on_store = index_increment_on_store;
+        }
+
+        quirks
+    }
+}
+
+fn invalid(key: &str) -> String
+{
+    format!("Invalid Value For {}", key)
+}
+
+fn string<'a>(table: &'a Value, key: &str) -> Result<Option<&'a str>, String>
+{
+    table.get(key).map(|value| value.as_str().ok_or_else(|| invalid(key))).transpose()
+}
+
+fn boolean(table: &Value, key: &str) -> Result<Option<bool>, String>
+{
+    table.get(key).map(|value| value.as_bool().ok_or_else(|| invalid(key))).transpose()
+}
+
+// Whole numbers are accepted too, eg - scale = 10
+fn float(table: &Value, key: &str) -> Result<Option<f32>, String>
+{
+    table.get(key).map(|value|
+    {
+        value.as_float()
+             .or_else(|| value.as_integer().map(|i| i as f64))
+             .filter(|&f| f > 0.0)
+             .map(|f| f as f32)
+             .ok_or_else(|| invalid(key))
+    }).transpose()
+}
+
+fn integer(table: &Value, key: &str) -> Result<Option<u32>, String>
+{
+    table.get(key).map(|value|
+    {
+        value.as_integer()
+             .and_then(|i| u32::try_from(i).ok())
+             .ok_or_else(|| invalid(key))
+    }).transpose()
+}
+
+// Colors are written as "#RRGGBB"
+fn color(table: &Value, key: &str) -> Result<Option<Color>, String>
+{
+    let hex = match string(table, key)?
+    {
+        Some(hex) => hex,
+        None => return Ok(None),
+    };
+
+    let digits = hex.trim_start_matches('#');
+    if digits.len() != 6
+    {
+        return Err(invalid(key));
+    }
+
+    let rgb = u32::from_str_radix(digits, 16).map_err(|_| invalid(key))?;
+    Ok(Some(Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn missing_fields_keep_their_defaults()
+    {
+        let config = Config::parse("scale = 12\n[quirks]\ndisplay_wait = true\n").unwrap();
+
+        assert_eq!(config.scale, Some(12.0));
+        assert_eq!(config.cycles_per_frame, None);
+        assert_eq!(config.controls, None);
+
+        let quirks = config.quirks(None);
+        assert!(quirks.display_wait);
+        assert_eq!(quirks.wrap_sprites, Quirks::default().wrap_sprites);
+    }
+
+    #[test]
+    fn parses_every_field()
+    {
+        let text = "scale = 7.5\n\
+                    cycles_per_frame = 10\n\
+                    controls = \"x 1 2 3 q w e a s d z c 4 r f v\"\n\
+                    foreground = \"#33FF66\"\n\
+                    background = \"101010\"\n\
+                    platform = \"xochip\"\n\
+                    flags_dir = \"saves/flags\"\n";
+        let config = Config::parse(text).unwrap();
+
+        assert_eq!(config.scale, Some(7.5));
+        assert_eq!(config.cycles_per_frame, Some(10));
+        assert_eq!(config.controls.unwrap()[0], KeyCode::X);
+        assert_eq!(config.foreground, Some(Color::from_rgb(0x33, 0xFF, 0x66)));
+        assert_eq!(config.background, Some(Color::from_rgb(0x10, 0x10, 0x10)));
+        assert_eq!(config.flags_dir, Some(PathBuf::from("saves/flags")));
+        assert_eq!(config.quirks(None), Platform::XoChip.quirks());
+        assert_eq!(config.quirks(Some(Platform::CosmacVip)), Platform::CosmacVip.quirks());
+    }
+
+    #[test]
+    fn parses_index_increment_on_store()
+    {
+        let config = Config::parse("[quirks]\nindex_increment_on_store = \"unchanged\"\n").unwrap();
+
+        assert!(config.sets_quirks());
+        assert_eq!(config.index_increment_on_store, Some(IndexIncrement::Unchanged));
+        assert_eq!(config.quirks(Some(Platform::CosmacVip)).index_increment_on_store, IndexIncrement::Unchanged);
+
+        assert!(Config::parse("[quirks]\nindex_increment_on_store = \"y\"").is_err());
+        assert!(Config::parse("[quirks]\nindex_increment_on_store = 1").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values()
+    {
+        assert!(Config::parse("scale = \"big\"").is_err());
+        assert!(Config::parse("cycles_per_frame = -1").is_err());
+        assert!(Config::parse("foreground = \"#12345\"").is_err());
+        assert!(Config::parse("platform = \"gameboy\"").is_err());
+        assert!(Config::parse("quirks = 1").is_err());
+        assert!(Config::parse("scale = ").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::error::AsmError;
+use crate::instruction::{decode, Instruction::*};
+
+pub fn disassemble(opcode: u16) -> String
+{
+    disassemble_labelled(opcode, None)
+}
+
+// Jump and call targets are written as their label when there is one
+pub fn disassemble_labelled(opcode: u16, labels: Option<&HashMap<u16, String>>) -> String
+{
+    let target = |nnn: u16| match labels.and_then(|labels| labels.get(&nnn))
+    {
+        Some(label) => label.clone(),
+        None => format!("0x{:03X}", nnn),
+    };
+
+    match decode(opcode)
+    {
+        Clear => String::from("CLS"),
+        Return => String::from("RET"),
+        Exit => String::from("EXIT"),
+        Sys(nnn) => format!("SYS 0x{:03X}", nnn),
+
+        Jump(nnn) => format!("JP {}", target(nnn)),
+        Call(nnn) => format!("CALL {}", target(nnn)),
+
+        SkipEq { x, kk } => format!("SE V{:X}, 0x{:02X}", x, kk),
+        SkipNotEq { x, kk } => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        SkipEqReg { x, y } => format!("SE V{:X}, V{:X}", x, y),
+        SkipNotEqReg { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+
+        Load { x, kk } => format!("LD V{:X}, 0x{:02X}", x, kk),
+        Add { x, kk } => format!("ADD V{:X}, 0x{:02X}", x, kk),
+
+        Move { x, y } => format!("LD V{:X}, V{:X}", x, y),
+        Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+        And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+        Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+        AddReg { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+        Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+        ShiftRight { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+        SubN { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+        ShiftLeft { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+
+        LoadIndex(nnn) => format!("LD I, 0x{:03X}", nnn),
+        JumpOffset(nnn) => format!("JP V0, {}", target(nnn)),
+
+        Random { x, kk } => format!("RND V{:X}, 0x{:02X}", x, kk),
+        Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+
+        SkipKey { x } => format!("SKP V{:X}", x),
+        SkipNotKey { x } => format!("SKNP V{:X}", x),
+
+        LoadDelay { x } => format!("LD V{:X}, DT", x),
+        WaitKey { x } => format!("LD V{:X}, K", x),
+        SetDelay { x } => format!("LD DT, V{:X}", x),
+        SetSound { x } => format!("LD ST, V{:X}", x),
+        AddIndex { x } => format!("ADD I, V{:X}", x),
+        LoadFont { x } => format!("LD F, V{:X}", x),
+        LoadBigFont { x } => format!("LD HF, V{:X}", x),
+        StoreBcd { x } => format!("LD B, V{:X}", x),
+        StoreRegisters { x } => format!("LD [I], V{:X}", x),
+        LoadRegisters { x } => format!("LD V{:X}, [I]", x),
+        StoreFlags { x } => format!("LD R, V{:X}", x),
+        LoadFlags { x } => format!("LD V{:X}, R", x),
+
+        // Anything that isn't a valid instruction is most likely sprite data
+        Unknown(opcode) => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+pub fn disassemble_range(memory: &[u8], start: usize, end: usize, labels: Option<&HashMap<u16, String>>) -> Vec<(u16, String)>
+{
+    let end = end.min(memory.len());
+    let mut instructions = Vec::new();
+
+    let mut address = start;
+    while address + 1 < end
+    {
+        let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        instructions.push((address as u16, disassemble_labelled(opcode, labels)));
+
+        address += 2;
+    }
+
+    instructions
+}
+
+// A .sym file has one address and label per line, eg -
+//
+// 0x200 start
+// 22A   main_loop  ; comment
+pub fn parse_symbols(text: &str) -> Result<HashMap<u16, String>, AsmError>
+{
+    let mut labels = HashMap::new();
+
+    for (i, raw) in text.lines().enumerate()
+    {
+        let line = i + 1;
+        let mut words = raw.split(';').next().unwrap_or("").split_whitespace();
+
+        let (address, label) = match (words.next(), words.next(), words.next())
+        {
+            (None, _, _) => continue,
+            (Some(address), Some(label), None) => (address, label),
+            _ => return Err(AsmError::new(line, String::from("Expected An Address And A Label"))),
+        };
+
+        let digits = address.trim_start_matches("0x");
+        let address = u16::from_str_radix(digits, 16).map_err(|_| AsmError::new(line, format!("Invalid Address: {}", address)))?;
+
+        labels.insert(address, label.to_string());
+    }
+
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn known_opcodes()
+    {
+        let table =
+        [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x1228, "JP 0x228"),
+            (0x2ABC, "CALL 0xABC"),
+            (0x3A10, "SE VA, 0x10"),
+            (0x620A, "LD V2, 0x0A"),
+            (0x8AB4, "ADD VA, VB"),
+            (0x812E, "SHL V1, V2"),
+            (0xA050, "LD I, 0x050"),
+            (0xD015, "DRW V0, V1, 5"),
+            (0xE3A1, "SKNP V3"),
+            (0xF40A, "LD V4, K"),
+            (0xF565, "LD V5, [I]"),
+            (0x5121, "DW 0x5121"),
+        ];
+
+        for &(opcode, text) in table.iter()
+        {
+            assert_eq!(disassemble(opcode), text);
+        }
+    }
+
+    #[test]
+    fn range_reports_addresses()
+    {
+        let memory = [0x60, 0x05, 0x00, 0xE0, 0x12];
+
+        assert_eq!(disassemble_range(&memory, 0, memory.len(), None),
+                   vec![(0x0, String::from("LD V0, 0x05")), (0x2, String::from("CLS"))]);
+    }
+
+    #[test]
+    fn labels_replace_targets()
+    {
+        let labels = parse_symbols("0x200 start\n\n 20A main_loop ; the loop\n").unwrap();
+        let memory = [0x12, 0x0A, 0x22, 0x00, 0x13, 0x00];
+
+        let text: Vec<String> = disassemble_range(&memory, 0, memory.len(), Some(&labels))
+                                    .into_iter()
+                                    .map(|(_, text)| text)
+                                    .collect();
+
+        assert_eq!(text, ["JP main_loop", "CALL start", "JP 0x300"]);
+        assert!(parse_symbols("0x200").is_err());
+        assert!(parse_symbols("0xZZZ start").is_err());
+    }
+}
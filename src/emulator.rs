@@ -1,27 +1,160 @@
-use ggez::{conf,
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ggez::{audio::{self, SoundSource},
+    conf,
     Context, ContextBuilder,
-    event,
+    event::{self, EventHandler, winit_event::{ElementState, Event, KeyboardInput, WindowEvent}},
     graphics,
     input::{self, keyboard::KeyCode},
     timer};
+use gilrs::{EventType, Gilrs};
+
+use crate::error::{LoadError, MachineError};
+use crate::flags;
+use crate::frontend::{null::NullFrontend, Frontend};
+use crate::keymap;
+use crate::machine::{self, Chip8, SaveState};
+use crate::quirks::Platform;
+use crate::replay::{Playback, Recorder};
+use crate::rewind::RewindBuffer;
+use crate::tone::{self, Waveform};
+
+// Timers always tick at 60Hz, the CPU clock is spread across these ticks
+pub(crate) const TIMER_HZ: u32 = 60;
+
+// A quarter of a second behind is as much as gets caught up on by default
+const DEFAULT_MAX_CATCH_UP: u32 = 15;
+
+const PAUSE_KEY: KeyCode = KeyCode::P;
+const STEP_KEY: KeyCode = KeyCode::Space;
+const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+const TURBO_KEY: KeyCode = KeyCode::Tab;
+const REWIND_KEY: KeyCode = KeyCode::Back;
+const OVERLAY_KEY: KeyCode = KeyCode::F1;
+const MUTE_KEY: KeyCode = KeyCode::M;
+const STATS_KEY: KeyCode = KeyCode::F2;
+const AUTOFIRE_KEY: KeyCode = KeyCode::F3;
+const SLOWER_KEY: KeyCode = KeyCode::LBracket;
+const FASTER_KEY: KeyCode = KeyCode::RBracket;
+const GRID_KEY: KeyCode = KeyCode::G;
+const NEXT_ROM_KEY: KeyCode = KeyCode::PageDown;
+const PREV_ROM_KEY: KeyCode = KeyCode::PageUp;
+
+// F5 - F8 save to a slot and Shift + the same key loads it back
+const QUICKSLOT_KEYS: [KeyCode; NUM_QUICKSLOTS] = [KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8];
+pub const NUM_QUICKSLOTS: usize = 4;
+
+// How long a notice like "Saved Slot 1" stays on screen
+const NOTICE_TIME: Duration = Duration::from_secs(2);
+
+// The speeds the hotkeys step through
+const SPEED_PRESETS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
 
-use crate::machine::{self, Chip8};
+// Held autofire keys are pressed for 3 ticks then released for 3
+const DEFAULT_AUTOFIRE_INTERVAL: u32 = 3;
+
+// Roughly 40 seconds of history with a state kept every 4th frame
+const REWIND_STATES: usize = 600;
+const REWIND_INTERVAL: u32 = 4;
+const SCALE_UP_KEYS: [KeyCode; 2] = [KeyCode::Equals, KeyCode::Add];
+const SCALE_DOWN_KEYS: [KeyCode; 2] = [KeyCode::Minus, KeyCode::Subtract];
+
+const DEFAULT_TITLE: &str = "Chip-8 Emulator";
 
 const BG_COLOR: graphics::Color = graphics::Color::new(0.0, 0.0, 0.0, 1.0);
+const OVERLAY_COLOR: graphics::Color = graphics::Color::new(0.0, 0.0, 0.0, 0.6);
+const OVERLAY_TEXT_COLOR: graphics::Color = graphics::Color::new(0.2, 1.0, 0.2, 1.0);
+const OVERLAY_PADDING: f32 = 4.0;
+const MESSAGE_TEXT_COLOR: graphics::Color = graphics::Color::new(1.0, 1.0, 1.0, 1.0);
+const GRID_COLOR: graphics::Color = graphics::Color::new(0.5, 0.5, 0.5, 0.35);
+const GRID_LINE_WIDTH: f32 = 1.0;
+
+pub const NUM_COLORS: usize = 4;
+
+// Lit pixels that are erased take 3 ticks to fade out
+const DEFAULT_FADE_RATE: f32 = 0.34;
+
+const DEFAULT_PALETTE: [graphics::Color; NUM_COLORS] =
+[
+    graphics::Color::new(0.0, 0.0, 0.0, 1.0),
+    graphics::Color::new(1.0, 1.0, 1.0, 1.0),
+    graphics::Color::new(0.67, 0.67, 0.67, 1.0),
+    graphics::Color::new(0.33, 0.33, 0.33, 1.0),
+];
 
 pub struct Emulator
 {
     machine: Chip8,
+    clock_hz: u32,
+    cycle_remainder: u32,
+    step_elapsed: Duration,
+    max_catch_up: u32,
+    paused: bool,
+    turbo: bool,
+    turbo_multiplier: u32,
+    speed: f32,
+    speed_remainder: f32,
+    rewind: RewindBuffer,
+    rewinding: bool,
+    quickslots: [Option<SaveState>; NUM_QUICKSLOTS],
+    recorder: Option<Recorder>,
+    playback: Option<Playback>,
+    redraw: bool,
+    show_overlay: bool,
+    show_stats: bool,
+    show_grid: bool,
+    stats: Stats,
+    exit_on_halt: bool,
+    auto_quirks: bool,
+    pause_on_focus_loss: bool,
+    focus_paused: bool,
 
     scale: f32,
+    orientation: Orientation,
     width: f32,
     height: f32,
+    resize: bool,
+    vsync: bool,
+    target_fps: Option<u32>,
+    last_present: Instant,
 
     frame: [u8; 4 * machine::VIDEO_HEIGHT * machine::VIDEO_WIDTH],
+    frame_image: Option<graphics::Image>,
+    grid: Option<graphics::Mesh>,
+    palette: [graphics::Color; NUM_COLORS],
+    fade: bool,
+    fade_rate: f32,
+    fading: bool,
+    intensity: [f32; machine::VIDEO_BUFFER_SIZE],
+    frame_blend: bool,
+    blending: bool,
+    previous_video: [bool; machine::VIDEO_BUFFER_SIZE],
 
     controls: [input::keyboard::KeyCode; machine::NUM_KEYS],
+    keypad: [bool; machine::NUM_KEYS],
+    gamepad_controls: Vec<(event::Button, usize)>,
+    gamepad_keypad: [bool; machine::NUM_KEYS],
+    autofire: [Option<u32>; machine::NUM_KEYS],
+    autofire_interval: u32,
+    autofire_tick: u32,
+    key_poll_interval: u32,
+
+    beep_freq: f32,
+    waveform: Waveform,
+    beeping: bool,
+    beep_source: Option<audio::Source>,
+    volume: f32,
+    muted: bool,
 
+    rom_path: Option<PathBuf>,
+    flags_dir: Option<PathBuf>,
     window_title: String,
+    retitle: bool,
+    message: Option<String>,
+    notice: Option<(String, Instant)>,
 }
 
 impl Emulator
@@ -31,145 +164,1891 @@ impl Emulator
         Emulator
         {
             machine,
+            clock_hz: 8 * TIMER_HZ,
+            cycle_remainder: 0,
+            step_elapsed: Duration::from_secs(0),
+            max_catch_up: DEFAULT_MAX_CATCH_UP,
+            paused: false,
+            turbo: false,
+            turbo_multiplier: 4,
+            speed: 1.0,
+            speed_remainder: 0.0,
+            rewind: RewindBuffer::new(REWIND_STATES, REWIND_INTERVAL),
+            rewinding: false,
+            quickslots: Default::default(),
+            recorder: None,
+            playback: None,
+            redraw: true,
+            show_overlay: false,
+            show_stats: false,
+            show_grid: false,
+            stats: Stats::new(Instant::now()),
+            exit_on_halt: false,
+            auto_quirks: true,
+            pause_on_focus_loss: true,
+            focus_paused: false,
 
             scale,
+            orientation: Orientation::default(),
             width: scale * machine::VIDEO_WIDTH as f32,
             height: scale * machine::VIDEO_HEIGHT as f32,
+            resize: false,
+            vsync: true,
+            target_fps: None,
+            last_present: Instant::now(),
 
             frame: [255; 4 * machine::VIDEO_WIDTH * machine::VIDEO_HEIGHT],
+            frame_image: None,
+            grid: None,
+            palette: DEFAULT_PALETTE,
+            fade: false,
+            fade_rate: DEFAULT_FADE_RATE,
+            fading: false,
+            intensity: [0.0; machine::VIDEO_BUFFER_SIZE],
+            frame_blend: false,
+            blending: false,
+            previous_video: [false; machine::VIDEO_BUFFER_SIZE],
+
+            controls: keymap::DEFAULT_CONTROLS,
+            keypad: [false; machine::NUM_KEYS],
+            gamepad_controls: keymap::DEFAULT_GAMEPAD_CONTROLS.to_vec(),
+            gamepad_keypad: [false; machine::NUM_KEYS],
+            autofire: [None; machine::NUM_KEYS],
+            autofire_interval: DEFAULT_AUTOFIRE_INTERVAL,
+            autofire_tick: 0,
+            key_poll_interval: 0,
+
+            beep_freq: 440.0,
+            waveform: Waveform::Square,
+            beeping: false,
+            beep_source: None,
+            volume: 1.0,
+            muted: false,
+
+            rom_path: None,
+            flags_dir: None,
+            window_title: String::from(DEFAULT_TITLE),
+            retitle: false,
+            message: None,
+            notice: None,
+        }
+    }
+
+    pub fn set_clock_hz(&mut self, hz: u32)
+    {
+        self.clock_hz = hz;
+        self.cycle_remainder = 0;
+    }
+
+    // When the host falls more than this many 60Hz ticks behind the rest are
+    // dropped, the game slows down for a moment instead of running a burst of
+    // frames to catch up. Larger values keep the timing exact for longer
+    pub fn set_max_catch_up(&mut self, ticks: u32)
+    {
+        self.max_catch_up = ticks.max(1);
+    }
+
+    pub fn set_turbo_multiplier(&mut self, multiplier: u32)
+    {
+        self.turbo_multiplier = multiplier.max(1);
+    }
+
+    // Scales the whole machine, timers included, eg - at 0.5 every other
+    // tick runs a frame and at 2.0 every tick runs two
+    pub fn set_speed(&mut self, factor: f32)
+    {
+        self.speed = factor.max(0.01).min(SPEED_PRESETS[SPEED_PRESETS.len() - 1]);
+        self.speed_remainder = 0.0;
+    }
+
+    pub fn set_rewind_capacity(&mut self, states: usize)
+    {
+        self.rewind.set_capacity(states);
+    }
+
+    // Logs every keypad change from now on, see save_recording
+    pub fn start_recording(&mut self)
+    {
+        self.recorder = Some(Recorder::new());
+    }
+
+    pub fn save_recording(&self, path: &str) -> std::io::Result<()>
+    {
+        match &self.recorder
+        {
+            Some(recorder) => recorder.save(path),
+            None => Ok(()),
+        }
+    }
+
+    // Live input is ignored while a replay is playing
+    pub fn set_playback(&mut self, playback: Playback)
+    {
+        self.playback = Some(playback);
+    }
+
+    // Close the window once the ROM halts instead of leaving the last frame up
+    pub fn set_exit_on_halt(&mut self, exit: bool)
+    {
+        self.exit_on_halt = exit;
+    }
+
+    // Known ROMs get their platform's quirks when loaded, turn this off
+    // to keep the quirks that were set by hand
+    pub fn set_auto_quirks(&mut self, auto: bool)
+    {
+        self.auto_quirks = auto;
+    }
+
+    // Turn off to keep running in the background
+    pub fn set_pause_on_focus_loss(&mut self, pause: bool)
+    {
+        self.pause_on_focus_loss = pause;
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles: u32)
+    {
+        self.set_clock_hz(cycles * TIMER_HZ);
+    }
+
+    // The window itself is resized on the next draw since that needs a Context
+    pub fn set_scale(&mut self, scale: f32)
+    {
+        let (width, height) = self.orientation.dimensions();
+
+        self.scale = scale.max(1.0);
+        self.width = self.scale * width as f32;
+        self.height = self.scale * height as f32;
+        self.resize = true;
+        self.frame_image = None;
+        self.grid = None;
+    }
+
+    pub fn set_grid(&mut self, show_grid: bool)
+    {
+        self.show_grid = show_grid;
+    }
+
+    // Only the picture is turned, the keypad stays as it is
+    pub fn set_orientation(&mut self, orientation: Orientation)
+    {
+        self.orientation = orientation;
+        self.set_scale(self.scale);
+        self.redraw = true;
+    }
+
+    // Only read when the window is created
+    pub fn set_vsync(&mut self, vsync: bool)
+    {
+        self.vsync = vsync;
+    }
+
+    // Caps how often the window is redrawn, 0 removes the cap. The CPU
+    // and timers keep running at their own rate whatever this is set to
+    pub fn set_target_fps(&mut self, fps: u32)
+    {
+        self.target_fps = match fps
+        {
+            0 => None,
+            fps => Some(fps),
+        };
+    }
+
+    // Takes effect the next time the sound timer starts
+    pub fn set_beep(&mut self, freq_hz: f32, waveform: Waveform)
+    {
+        self.beep_freq = freq_hz;
+        self.waveform = waveform;
+        self.beep_source = None;
+    }
+
+    pub fn set_volume(&mut self, volume: f32)
+    {
+        self.volume = volume.max(0.0).min(1.0);
+
+        if let Some(source) = &mut self.beep_source
+        {
+            source.set_volume(self.volume);
+        }
+    }
+
+    // The sound timer keeps running while muted, only the output is silenced
+    pub fn toggle_mute(&mut self)
+    {
+        self.muted = !self.muted;
+    }
+
+    pub fn set_controls(&mut self, controls: [KeyCode; machine::NUM_KEYS])
+    {
+        self.controls = controls;
+        self.keypad = [false; machine::NUM_KEYS];
+    }
+
+    // Pairs of a gamepad button and the keypad index it presses
+    pub fn set_gamepad_controls(&mut self, controls: Vec<(event::Button, usize)>)
+    {
+        self.gamepad_controls = controls;
+        self.gamepad_keypad = [false; machine::NUM_KEYS];
+    }
+
+    // While held the key toggles every interval ticks, None turns it off
+    pub fn set_autofire(&mut self, key: usize, interval: Option<u32>)
+    {
+        if let Some(autofire) = self.autofire.get_mut(key)
+        {
+            *autofire = interval.map(|ticks| ticks.max(1));
+        }
+    }
+
+    // Refreshes the keypad every this many cycles within a frame as well
+    // as at the start of it, 0 only polls once per frame
+    pub fn set_key_poll_interval(&mut self, cycles: u32)
+    {
+        self.key_poll_interval = cycles;
+    }
+
+    // The interval used when autofire is turned on with the hotkey
+    pub fn set_autofire_interval(&mut self, ticks: u32)
+    {
+        self.autofire_interval = ticks.max(1);
+    }
+
+    pub fn palette(&self) -> [graphics::Color; NUM_COLORS]
+    {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: [graphics::Color; NUM_COLORS])
+    {
+        self.palette = palette;
+        self.redraw = true;
+        self.frame_image = None;
+    }
+
+    // Erased pixels fade out like phosphor instead of switching off,
+    // which hides most of the flicker from XOR drawing
+    pub fn set_fade(&mut self, fade: bool)
+    {
+        self.fade = fade;
+        self.redraw = true;
+    }
+
+    // Shows every pixel set in either this frame or the last one, which
+    // hides the flicker of games that erase and redraw every frame
+    pub fn set_frame_blend(&mut self, frame_blend: bool)
+    {
+        self.frame_blend = frame_blend;
+        self.blending = false;
+        self.previous_video = [false; machine::VIDEO_BUFFER_SIZE];
+        self.redraw = true;
+    }
+
+    // How much of its brightness an erased pixel loses each tick
+    pub fn set_fade_rate(&mut self, rate: f32)
+    {
+        self.fade_rate = rate.max(0.01).min(1.0);
+    }
+
+    // Slots only last as long as the Emulator, they aren't written to disk
+    pub fn save_quickslot(&mut self, slot: usize)
+    {
+        if let Some(quickslot) = self.quickslots.get_mut(slot)
+        {
+            *quickslot = Some(self.machine.save_state());
+            self.notify(format!("Saved Slot {}", slot + 1));
+        }
+    }
+
+    // Returns whether there was a state in the slot to load
+    pub fn load_quickslot(&mut self, slot: usize) -> bool
+    {
+        match self.quickslots.get(slot)
+        {
+            Some(Some(state)) =>
+            {
+                self.machine.load_state(state);
+                self.redraw = true;
+                self.notify(format!("Loaded Slot {}", slot + 1));
+                true
+            },
+            _ =>
+            {
+                self.notify(format!("Slot {} Is Empty", slot + 1));
+                false
+            },
+        }
+    }
+
+    pub fn toggle_pause(&mut self)
+    {
+        self.paused = !self.paused;
+        self.focus_paused = false;
+
+        // Only kept while stepping by hand, it costs a save state per instruction
+        if !self.paused
+        {
+            self.machine.set_step_history(false);
+        }
+    }
+
+    pub fn step(&mut self)
+    {
+        if !self.paused
+        {
+            return;
+        }
+
+        // Already stopped after every instruction, so step over breakpoints
+        // and don't wait for vblank after a draw
+        self.machine.set_step_history(true);
+        self.machine.vblank();
+        let result = match self.machine.cycle()
+        {
+            Err(MachineError::Breakpoint(_)) => self.machine.cycle(),
+            result => result,
+        };
+
+        match result
+        {
+            Ok(_) | Err(MachineError::ConditionMet(..)) => (),
+            Err(e) => self.notify(e.to_string()),
+        }
+        self.redraw = true;
+    }
+
+    // Undoes the instructions run with step, one at a time
+    pub fn step_back(&mut self)
+    {
+        if self.paused && self.machine.step_back()
+        {
+            self.redraw = true;
+        }
+    }
+
+    // Saves the screen at the current scale and palette, returning the file name
+    pub fn screenshot(&self) -> image::ImageResult<String>
+    {
+        let scale = self.scale.max(1.0) as u32;
+        let width = machine::VIDEO_WIDTH as u32 * scale;
+        let height = machine::VIDEO_HEIGHT as u32 * scale;
+
+        let mut buffer = Vec::with_capacity((3 * width * height) as usize);
+        for y in 0..height
+        {
+            for x in 0..width
+            {
+                let pixel = self.machine.pixel((x / scale) as usize, (y / scale) as usize);
+                let (r, g, b) = self.palette[pixel as usize].to_rgb();
+
+                buffer.extend_from_slice(&[r, g, b]);
+            }
+        }
 
-            controls: [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
-                       KeyCode::Q,    KeyCode::W,    KeyCode::E,    KeyCode::R,
-                       KeyCode::A,    KeyCode::S,    KeyCode::D,    KeyCode::F,
-                       KeyCode::Z,    KeyCode::X,    KeyCode::C,    KeyCode::V],
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+                                         .map(|time| time.as_millis())
+                                         .unwrap_or(0);
+        let path = format!("screenshot-{}.png", timestamp);
 
-            window_title: String::from("Chip-8 Emulator"),
+        image::save_buffer(&path, &buffer, width, height, image::ColorType::Rgb8)?;
+
+        Ok(path)
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<(), LoadError>
+    {
+        self.load_at(path, machine::ROM_MEMORY_START)
+    }
+
+    pub fn load_at(&mut self, path: &str, address: u16) -> Result<(), LoadError>
+    {
+        self.rewind.clear();
+        self.machine.load_at(path, address)?;
+        self.rom_path = Some(PathBuf::from(path));
+        self.restore_flags();
+        self.detect_quirks(Path::new(path));
+        self.set_window_title(title_for(Path::new(path)));
+        Ok(())
+    }
+
+    // There's no file name to title the window with, so it goes back to the default
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError>
+    {
+        self.rewind.clear();
+        self.machine.load_bytes(rom)?;
+        self.rom_path = None;
+        self.set_window_title(String::from(DEFAULT_TITLE));
+        Ok(())
+    }
+
+    // The RPL flags of each ROM are kept here between runs, set it
+    // before loading so the first ROM's flags are picked up
+    pub fn set_flags_dir(&mut self, dir: Option<PathBuf>)
+    {
+        self.flags_dir = dir;
+    }
+
+    // Steps through the .ch8 files in the ROM's directory by name,
+    // wrapping around at either end
+    pub fn load_next(&mut self)
+    {
+        self.load_adjacent(true);
+    }
+
+    pub fn load_prev(&mut self)
+    {
+        self.load_adjacent(false);
+    }
+
+    // Shown once the window is open, or on the next update if it already is
+    pub fn set_window_title(&mut self, title: String)
+    {
+        self.window_title = title;
+        self.retitle = true;
+    }
+
+    // Runs at the configured clock without a window or any waiting,
+    // stopping early if the program halts
+    pub fn run_headless(&mut self, frames: usize) -> Result<(), MachineError>
+    {
+        let mut frontend = NullFrontend;
+
+        for _i in 0..frames
+        {
+            let cycles = self.cycles_this_tick();
+            match self.machine.run_frame(&mut frontend, cycles)
+            {
+                Ok(()) => (),
+                Err(MachineError::Halted) => break,
+                Err(e) => return Err(e),
+            }
         }
+
+        self.redraw = true;
+        Ok(())
     }
 
-    pub fn load(&mut self, path: &str)
+    // Shows the message instead of running the machine, for errors
+    // that should be seen in the window rather than on a terminal
+    pub fn show_message(&mut self, message: String)
     {
-        self.machine.load(path);
+        self.message = Some(message);
     }
 
+    // Gamepads are read through gilrs directly since ggez only hands
+    // its gamepad events to event::run
     pub fn create_display(&mut self)
     {
         let (ctx, event_loop) = &mut ContextBuilder::new("Chip-8 Emulator", "Shaleen Baral")
-                                        .window_setup(conf::WindowSetup::default().title(&self.window_title).vsync(true))
+                                        .window_setup(conf::WindowSetup::default().title(&self.window_title).vsync(self.vsync))
                                         .window_mode(conf::WindowMode::default().dimensions(self.width, self.height))
+                                        .modules(conf::ModuleConf::default().gamepad(false))
                                         .build().expect("Error Creating Context!");
 
-        event::run(ctx, event_loop, self).expect("Error Running Emulator");
+        let gamepads = Gilrs::new().ok();
+
+        self.run(ctx, event_loop, gamepads).expect("Error Running Emulator");
+        self.persist_flags();
     }
 
-    fn update_buffer(&mut self)
+    // Same as event::run, except that dropped files are passed on to switch_rom
+    fn run(&mut self, ctx: &mut Context, event_loop: &mut event::EventsLoop, mut gamepads: Option<Gilrs>) -> ggez::GameResult
     {
-        for y in 0..machine::VIDEO_HEIGHT
+        while ctx.continuing
         {
-            for x in 0..machine::VIDEO_WIDTH
+            ctx.timer_context.tick();
+
+            event_loop.poll_events(|e|
             {
-                let index = y * machine::VIDEO_WIDTH + x;
-                let start = 4 * index;
+                ctx.process_event(&e);
 
-                if self.machine.video[index]
+                if let Event::WindowEvent { event: window_event, .. } = e
                 {
-                    self.frame[start] = 255;
-                    self.frame[start + 1] = 255;
-                    self.frame[start + 2] = 255;
+                    match window_event
+                    {
+                        WindowEvent::CloseRequested =>
+                        {
+                            if !self.quit_event(ctx)
+                            {
+                                event::quit(ctx);
+                            }
+                        },
+                        WindowEvent::Focused(gained) => self.focus_event(ctx, gained),
+                        WindowEvent::DroppedFile(path) => self.switch_rom(&path),
+                        WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(keycode), modifiers, .. }, .. } =>
+                        {
+                            match state
+                            {
+                                ElementState::Pressed =>
+                                {
+                                    let repeat = input::keyboard::is_key_repeated(ctx);
+                                    self.key_down_event(ctx, keycode, modifiers.into(), repeat);
+                                },
+                                ElementState::Released => self.key_up_event(ctx, keycode, modifiers.into()),
+                            }
+                        },
+                        _ => (),
+                    }
                 }
-                else
+            });
+
+            if let Some(gamepads) = &mut gamepads
+            {
+                while let Some(gilrs::Event { event: gamepad_event, .. }) = gamepads.next_event()
                 {
-                    self.frame[start] = 0;
-                    self.frame[start + 1] = 0;
-                    self.frame[start + 2] = 0;
+                    match gamepad_event
+                    {
+                        EventType::ButtonPressed(button, _) => self.set_gamepad_button(button, true),
+                        EventType::ButtonReleased(button, _) => self.set_gamepad_button(button, false),
+                        _ => (),
+                    }
                 }
             }
+
+            self.update(ctx)?;
+            self.draw(ctx)?;
+
+            timer::yield_now();
         }
+
+        Ok(())
     }
 
-    fn display_buffer(&self, ctx: &mut Context)
+    // Starts the dropped ROM from scratch, a file that can't be loaded
+    // is shown in the window like a bad ROM on the command line
+    // Goes through load so gzipped and Intel HEX ROMs can be dropped too
+    fn switch_rom(&mut self, path: &Path)
     {
-        // Perhaps you could store frame_image and update it only when
-        // the buffer updates but the performance is already so good that
-        // the added memory overhead may not be worth it
-        // and be also be slightly annoying to implement since we don't have
-        // a ggez Context when the struct is initialized from Emulator::new()
-        let mut frame_image = graphics::Image::from_rgba8(ctx,
-                                machine::VIDEO_WIDTH as u16,
-                                machine::VIDEO_HEIGHT as u16,
-                                &self.frame)
-                                .expect("Error Creating Frame");
+        self.persist_flags();
+        self.machine.reset();
 
-        frame_image.set_filter(graphics::FilterMode::Nearest);
+        match self.machine.load(&path.to_string_lossy())
+        {
+            Ok(()) =>
+            {
+                self.rewind.clear();
+                self.message = None;
+                self.redraw = true;
+                self.rom_path = Some(path.to_path_buf());
+                self.restore_flags();
+                self.detect_quirks(path);
+                self.set_window_title(title_for(path));
+            },
+            Err(error) => self.show_message(error.to_string()),
+        }
+    }
 
-        graphics::draw(ctx,
-                       &frame_image,
-                       graphics::DrawParam::default().scale([self.scale, self.scale]))
-                       .expect("Error Drawing Frame");
+    fn flags_path(&self) -> Option<PathBuf>
+    {
+        flags::path_for(self.flags_dir.as_ref()?, self.rom_path.as_ref()?)
     }
-}
 
-impl event::EventHandler for Emulator
-{
-    fn update(&mut self, ctx: &mut Context) -> ggez::GameResult
+    fn restore_flags(&mut self)
     {
+        if let Some(path) = self.flags_path()
+        {
+            self.machine.set_rpl_flags(flags::load(&path));
+        }
+    }
 
-        while timer::check_update_time(ctx, 60)
+    fn persist_flags(&mut self)
+    {
+        if let Some(path) = self.flags_path()
         {
-            for _i in 0..8
+            if let Err(error) = flags::save(&path, self.machine.rpl_flags())
             {
-                self.machine.fetch_and_execute();
+                self.notify(format!("Error Saving Flags {}: {}", path.display(), error));
             }
-            self.machine.decrement_timers();
         }
-
-        Ok(())
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> ggez::GameResult
+    fn load_adjacent(&mut self, forward: bool)
     {
-        graphics::clear(ctx, BG_COLOR);
+        let current = match &self.rom_path
+        {
+            Some(path) => path.clone(),
+            None => return,
+        };
 
-        if self.machine.redraw
+        if let Some(path) = adjacent_rom(&roms_beside(&current), &current, forward)
         {
-            self.update_buffer();
+            self.switch_rom(&path);
         }
+    }
 
-        self.display_buffer(ctx);
-
-        graphics::present(ctx).expect("Error Presenting");
+    fn detect_quirks(&mut self, path: &Path)
+    {
+        let platform = path.file_stem().and_then(|stem| Platform::detect(&stem.to_string_lossy()));
 
-        Ok(())
+        if let (true, Some(platform)) = (self.auto_quirks, platform)
+        {
+            self.machine.set_quirks(platform.quirks());
+        }
     }
 
-    fn key_down_event(&mut self, _ctx: &mut Context, keycode: input::keyboard::KeyCode, _keymods: input::keyboard::KeyMods, repeat: bool)
+    fn toggle_held_autofire(&mut self)
     {
-        if repeat
+        let held = self.poll_input();
+        for (autofire, _) in self.autofire.iter_mut().zip(held.iter()).filter(|&(_, &pressed)| pressed)
         {
-            return;
+            *autofire = match autofire
+            {
+                Some(_) => None,
+                None => Some(self.autofire_interval),
+            };
         }
+    }
 
-        for i in 0..machine::NUM_KEYS
+    fn apply_autofire(&mut self, keys: &mut [bool; machine::NUM_KEYS])
+    {
+        self.mask_autofire(keys, self.autofire_tick);
+        self.autofire_tick = self.autofire_tick.wrapping_add(1);
+    }
+
+    fn mask_autofire(&self, keys: &mut [bool; machine::NUM_KEYS], tick: u32)
+    {
+        for (key, autofire) in keys.iter_mut().zip(self.autofire.iter())
         {
-            if self.controls[i] == keycode
+            if let Some(interval) = autofire
             {
-                self.machine.keypad[i] = true;
-                return;
+                *key &= (tick / interval) % 2 == 0;
             }
         }
     }
 
-    fn key_up_event(&mut self,_ctx: &mut Context, keycode: input::keyboard::KeyCode, _keymods: input::keyboard::KeyMods)
+    // Autofire keeps the phase the frame started with, it only moves on once per frame
+    fn poll_keys_mid_frame(&mut self)
     {
+        let mut keys = self.poll_input();
+        self.mask_autofire(&mut keys, self.autofire_tick.wrapping_sub(1));
 
-        for i in 0..machine::NUM_KEYS
+        for (i, &pressed) in keys.iter().enumerate()
         {
-            if self.controls[i] == keycode
+            self.machine.set_key(i, pressed);
+        }
+    }
+
+    // Replays hold one set of keys per frame, so recording and playback
+    // stay at once per frame
+    fn polls_mid_frame(&self, cycle: u32) -> bool
+    {
+        self.key_poll_interval > 0
+            && cycle > 0
+            && cycle % self.key_poll_interval == 0
+            && self.playback.is_none()
+            && self.recorder.is_none()
+    }
+
+    fn set_gamepad_button(&mut self, btn: event::Button, pressed: bool)
+    {
+        for &(button, key) in &self.gamepad_controls
+        {
+            if button == btn && key < machine::NUM_KEYS
             {
-                self.machine.keypad[i] = false;
-                return;
+                self.gamepad_keypad[key] = pressed;
             }
+        }
+    }
+
+    // Carries the fractional part over so clocks that aren't a multiple
+    // of 60Hz still average out to the requested speed
+    fn cycles_this_tick(&mut self) -> u32
+    {
+        let clock_hz = if self.turbo
+        {
+            self.clock_hz * self.turbo_multiplier
+        }
+        else
+        {
+            self.clock_hz
+        };
+
+        let total = clock_hz + self.cycle_remainder;
+        self.cycle_remainder = total % TIMER_HZ;
+        total / TIMER_HZ
+    }
+
+    fn frames_this_tick(&mut self) -> u32
+    {
+        self.speed_remainder += self.speed;
+        let frames = self.speed_remainder as u32;
+        self.speed_remainder -= frames as f32;
+        frames
+    }
+
+    fn step_speed(&mut self, faster: bool)
+    {
+        let next = if faster
+        {
+            SPEED_PRESETS.iter().find(|&&preset| preset > self.speed)
+        }
+        else
+        {
+            SPEED_PRESETS.iter().rev().find(|&&preset| preset < self.speed)
+        };
+
+        if let Some(&speed) = next
+        {
+            self.set_speed(speed);
+            self.notify(format!("Speed: {}x", speed));
+        }
+    }
+
+    // Input, then a batch of cycles and a rewind snapshot, the same
+    // no matter how many of these run per 60Hz tick
+    fn emulate_frame(&mut self)
+    {
+        match &mut self.playback
+        {
+            Some(playback) => playback.apply(&mut self.machine),
+            None =>
+            {
+                let mut keys = self.poll_input();
+                self.apply_autofire(&mut keys);
+                for (i, &pressed) in keys.iter().enumerate()
+                {
+                    self.machine.set_key(i, pressed);
+                }
+            },
+        }
+
+        if let Some(recorder) = &mut self.recorder
+        {
+            let mut keys = [false; machine::NUM_KEYS];
+            for (i, key) in keys.iter_mut().enumerate()
+            {
+                *key = self.machine.key_state(i);
+            }
+            recorder.record(&keys);
+        }
+        self.machine.apply_cheats();
+
+        self.machine.vblank();
+        for i in 0..self.cycles_this_tick()
+        {
+            // A draw ends the batch early with the display_wait quirk
+            if self.machine.is_waiting_for_vblank()
+            {
+                break;
+            }
+
+            if self.polls_mid_frame(i)
+            {
+                self.poll_keys_mid_frame();
+            }
+
+            match self.machine.cycle()
+            {
+                Ok(changed) => self.redraw |= changed,
+                Err(MachineError::Halted) => break,
+                Err(e @ MachineError::Breakpoint(_)) | Err(e @ MachineError::ConditionMet(..)) =>
+                {
+                    self.notify(e.to_string());
+                    self.paused = true;
+                    self.show_overlay = true;
+                    self.redraw = true;
+                    break;
+                },
+                Err(e) =>
+                {
+                    self.notify(e.to_string());
+                    self.paused = true;
+                    break;
+                }
+            }
+        }
+
+        self.rewind.record(&self.machine);
+    }
+
+    // How many whole 60Hz steps have built up, anything past
+    // max_catch_up steps behind is dropped rather than run later
+    fn fixed_steps(&mut self, elapsed: Duration) -> u32
+    {
+        let step = Duration::from_secs(1) / TIMER_HZ;
+
+        self.step_elapsed = (self.step_elapsed + elapsed).min(step * self.max_catch_up);
+
+        let steps = (self.step_elapsed.as_nanos() / step.as_nanos()) as u32;
+        self.step_elapsed -= step * steps;
+        steps
+    }
+
+    // Every step runs the CPU batch and the timers together, so the
+    // timers stay in line with the CPU at any speed. Returns whether
+    // the program halted and the window should close
+    fn advance(&mut self, elapsed: Duration) -> bool
+    {
+        for _i in 0..self.fixed_steps(elapsed)
+        {
+            if self.paused
+            {
+                continue;
+            }
+
+            if self.rewinding
+            {
+                self.redraw |= self.rewind.rewind(&mut self.machine);
+                continue;
+            }
+
+            if self.machine.is_halted()
+            {
+                if self.exit_on_halt
+                {
+                    return true;
+                }
+
+                self.machine.decrement_timers();
+                continue;
+            }
+
+            // Slow motion skips steps and fast forward runs several per step
+            for _i in 0..self.frames_this_tick()
+            {
+                // A breakpoint or error part way through pauses the rest
+                if self.paused
+                {
+                    break;
+                }
+
+                self.emulate_frame();
+                self.machine.decrement_timers();
+            }
+
+            // Fading pixels keep changing after the machine stops drawing,
+            // and a blended frame still shows pixels erased a frame ago
+            self.redraw |= self.fading || self.blending;
+        }
+
+        false
+    }
+
+    // Only undoes a pause the focus loss caused, so a game paused by
+    // hand stays paused when the window comes back
+    fn focus_changed(&mut self, gained: bool)
+    {
+        if gained
+        {
+            if self.focus_paused
+            {
+                self.paused = false;
+                self.focus_paused = false;
+            }
+        }
+        else if self.pause_on_focus_loss && !self.paused
+        {
+            self.paused = true;
+            self.focus_paused = true;
+        }
+    }
+
+    // The Source needs a Context so it is built the first time the tone plays
+    fn update_audio(&mut self, ctx: &mut Context) -> ggez::GameResult
+    {
+        if self.beep_source.is_none()
+        {
+            if !self.beeping || self.muted
+            {
+                return Ok(());
+            }
+
+            let data = audio::SoundData::from_bytes(&tone::wav(self.beep_freq, self.waveform));
+            let mut source = audio::Source::from_data(ctx, data)?;
+            source.set_repeat(true);
+            source.set_volume(self.volume);
+            self.beep_source = Some(source);
+        }
+
+        let audible = self.beeping && !self.muted;
+        if let Some(source) = &mut self.beep_source
+        {
+            if audible && source.paused()
+            {
+                source.resume();
+            }
+            else if audible && !source.playing()
+            {
+                source.play()?;
+            }
+            else if !audible && source.playing()
+            {
+                source.pause();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_buffer(&mut self)
+    {
+        let video = self.displayed_video();
+
+        if self.fade
+        {
+            self.fading = false;
+            for (level, &pixel) in self.intensity.iter_mut().zip(video.iter())
+            {
+                if pixel
+                {
+                    *level = 1.0;
+                }
+                else
+                {
+                    *level = (*level - self.fade_rate).max(0.0);
+                    self.fading |= *level > 0.0;
+                }
+            }
+        }
+
+        self.present(&video);
+    }
+
+    fn displayed_video(&mut self) -> [bool; machine::VIDEO_BUFFER_SIZE]
+    {
+        let mut video = [false; machine::VIDEO_BUFFER_SIZE];
+        video.copy_from_slice(self.machine.video_buffer());
+
+        if !self.frame_blend
+        {
+            return video;
+        }
+
+        let mut blended = video;
+        for (pixel, &previous) in blended.iter_mut().zip(self.previous_video.iter())
+        {
+            *pixel |= previous;
+        }
+        self.previous_video = video;
+        self.blending = blended[..] != video[..];
+
+        blended
+    }
+
+    fn draw_overlay(&self, ctx: &mut Context) -> ggez::GameResult
+    {
+        let mut overlay = String::new();
+
+        for (row, values) in self.machine.registers().chunks(4).enumerate()
+        {
+            for (column, value) in values.iter().enumerate()
+            {
+                overlay.push_str(&format!("V{:X}: {:02X}  ", 4 * row + column, value));
+            }
+            overlay.push('\n');
+        }
+
+        overlay.push_str(&format!("I: {:03X}  PC: {:03X}  SP: {:X}  DT: {:02X}  ST: {:02X}",
+                                  self.machine.index(),
+                                  self.machine.program_counter(),
+                                  self.machine.stack_pointer(),
+                                  self.machine.delay_timer(),
+                                  self.machine.sound_timer()));
+
+        let text = graphics::Text::new(overlay);
+        let (width, height) = text.dimensions(ctx);
+
+        let background = graphics::Mesh::new_rectangle(ctx,
+                            graphics::DrawMode::fill(),
+                            graphics::Rect::new(0.0, 0.0,
+                                                width as f32 + 2.0 * OVERLAY_PADDING,
+                                                height as f32 + 2.0 * OVERLAY_PADDING),
+                            OVERLAY_COLOR)?;
+
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx,
+                       &text,
+                       graphics::DrawParam::default()
+                           .dest([OVERLAY_PADDING, OVERLAY_PADDING])
+                           .color(OVERLAY_TEXT_COLOR))
+    }
+
+    fn draw_stats(&self, ctx: &mut Context) -> ggez::GameResult
+    {
+        let text = graphics::Text::new(format!("FPS: {:.1} ({:.1}ms)  IPS: {:.0}",
+                                               self.stats.fps,
+                                               self.stats.frame_ms,
+                                               self.stats.ips));
+        let (width, height) = text.dimensions(ctx);
+        let left = self.width - width as f32 - 2.0 * OVERLAY_PADDING;
+
+        let background = graphics::Mesh::new_rectangle(ctx,
+                            graphics::DrawMode::fill(),
+                            graphics::Rect::new(left, 0.0,
+                                                width as f32 + 2.0 * OVERLAY_PADDING,
+                                                height as f32 + 2.0 * OVERLAY_PADDING),
+                            OVERLAY_COLOR)?;
+
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx,
+                       &text,
+                       graphics::DrawParam::default()
+                           .dest([left + OVERLAY_PADDING, OVERLAY_PADDING])
+                           .color(OVERLAY_TEXT_COLOR))
+    }
+
+    // One line along every boundary between logical pixels, the edges
+    // of the window are left out
+    fn grid_lines(&self) -> Vec<[[f32; 2]; 2]>
+    {
+        let (width, height) = self.orientation.dimensions();
+
+        let columns = (1..width).map(|x| x as f32 * self.scale)
+                                .map(|x| [[x, 0.0], [x, self.height]]);
+        let rows = (1..height).map(|y| y as f32 * self.scale)
+                              .map(|y| [[0.0, y], [self.width, y]]);
+
+        columns.chain(rows).collect()
+    }
+
+    fn draw_grid(&mut self, ctx: &mut Context) -> ggez::GameResult
+    {
+        if self.grid.is_none()
+        {
+            let mut builder = graphics::MeshBuilder::new();
+            for line in self.grid_lines()
+            {
+                builder.line(&line, GRID_LINE_WIDTH, GRID_COLOR)?;
+            }
+
+            self.grid = Some(builder.build(ctx)?);
+        }
+
+        if let Some(grid) = &self.grid
+        {
+            graphics::draw(ctx, grid, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+
+    fn notify(&mut self, notice: String)
+    {
+        self.notice = Some((notice, Instant::now()));
+    }
+
+    fn draw_notice(&self, ctx: &mut Context, notice: &str) -> ggez::GameResult
+    {
+        let text = graphics::Text::new(notice);
+        let (width, height) = text.dimensions(ctx);
+        let top = self.height - height as f32 - 2.0 * OVERLAY_PADDING;
+
+        let background = graphics::Mesh::new_rectangle(ctx,
+                            graphics::DrawMode::fill(),
+                            graphics::Rect::new(0.0, top,
+                                                width as f32 + 2.0 * OVERLAY_PADDING,
+                                                height as f32 + 2.0 * OVERLAY_PADDING),
+                            OVERLAY_COLOR)?;
+
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx,
+                       &text,
+                       graphics::DrawParam::default()
+                           .dest([OVERLAY_PADDING, top + OVERLAY_PADDING])
+                           .color(MESSAGE_TEXT_COLOR))
+    }
+
+    fn draw_message(&self, ctx: &mut Context, message: &str) -> ggez::GameResult
+    {
+        let mut text = graphics::Text::new(message);
+        text.set_bounds([self.width - 2.0 * OVERLAY_PADDING, f32::INFINITY], graphics::Align::Center);
+
+        let height = text.dimensions(ctx).1 as f32;
+        graphics::draw(ctx,
+                       &text,
+                       graphics::DrawParam::default()
+                           .dest([OVERLAY_PADDING, (self.height - height) / 2.0])
+                           .color(MESSAGE_TEXT_COLOR))
+    }
+
+    // The Image can only be built once there is a Context, so it is
+    // created lazily and rebuilt only after the frame buffer changes
+    fn display_buffer(&mut self, ctx: &mut Context)
+    {
+        if self.frame_image.is_none()
+        {
+            let (width, height) = self.orientation.dimensions();
+            let mut frame_image = graphics::Image::from_rgba8(ctx,
+                                    width as u16,
+                                    height as u16,
+                                    &self.frame)
+                                    .expect("Error Creating Frame");
+
+            frame_image.set_filter(graphics::FilterMode::Nearest);
+
+            self.frame_image = Some(frame_image);
+        }
+
+        if let Some(frame_image) = &self.frame_image
+        {
+            graphics::draw(ctx,
+                           frame_image,
+                           graphics::DrawParam::default().scale([self.scale, self.scale]))
+                           .expect("Error Drawing Frame");
+        }
+    }
+}
+
+fn blend(from: graphics::Color, to: graphics::Color, amount: f32) -> graphics::Color
+{
+    graphics::Color::new(from.r + (to.r - from.r) * amount,
+                         from.g + (to.g - from.g) * amount,
+                         from.b + (to.b - from.b) * amount,
+                         1.0)
+}
+
+// Rotations are clockwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation
+{
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+// The mirror flips the picture left to right before it is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Orientation
+{
+    pub rotation: Rotation,
+    pub mirror: bool,
+}
+
+impl Orientation
+{
+    pub fn new(rotation: Rotation, mirror: bool) -> Orientation
+    {
+        Orientation { rotation, mirror }
+    }
+
+    // The frame is taller than it is wide when turned on its side
+    pub fn dimensions(self) -> (usize, usize)
+    {
+        match self.rotation
+        {
+            Rotation::Quarter | Rotation::ThreeQuarters => (machine::VIDEO_HEIGHT, machine::VIDEO_WIDTH),
+            Rotation::None | Rotation::Half => (machine::VIDEO_WIDTH, machine::VIDEO_HEIGHT),
+        }
+    }
+
+    // Where pixel (x, y) of the video ends up in the frame
+    fn frame_index(self, x: usize, y: usize) -> usize
+    {
+        let x = if self.mirror
+        {
+            machine::VIDEO_WIDTH - 1 - x
+        }
+        else
+        {
+            x
+        };
+        let (width, _) = self.dimensions();
+
+        let (frame_x, frame_y) = match self.rotation
+        {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (machine::VIDEO_HEIGHT - 1 - y, x),
+            Rotation::Half => (machine::VIDEO_WIDTH - 1 - x, machine::VIDEO_HEIGHT - 1 - y),
+            Rotation::ThreeQuarters => (y, machine::VIDEO_WIDTH - 1 - x),
+        };
+
+        frame_y * width + frame_x
+    }
+}
+
+impl Default for Orientation
+{
+    fn default() -> Self
+    {
+        Orientation::new(Rotation::None, false)
+    }
+}
+
+// Averaged over a second at a time so the numbers are steady enough to read
+struct Stats
+{
+    since: Instant,
+    frames: u32,
+    instructions: u64,
+
+    fps: f64,
+    frame_ms: f64,
+    ips: f64,
+}
+
+impl Stats
+{
+    fn new(now: Instant) -> Stats
+    {
+        Stats
+        {
+            since: now,
+            frames: 0,
+            instructions: 0,
+
+            fps: 0.0,
+            frame_ms: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    fn record_frame(&mut self, instruction_count: u64, now: Instant)
+    {
+        self.frames += 1;
+
+        let elapsed = now.duration_since(self.since);
+        if elapsed < Duration::from_secs(1)
+        {
+            return;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.fps = self.frames as f64 / seconds;
+        self.frame_ms = 1000.0 * seconds / self.frames as f64;
+        // Loading a ROM resets the count
+        self.ips = instruction_count.saturating_sub(self.instructions) as f64 / seconds;
+
+        self.since = now;
+        self.frames = 0;
+        self.instructions = instruction_count;
+    }
+}
+
+// Sorted so that next and previous follow the order a file manager shows
+fn roms_beside(path: &Path) -> Vec<PathBuf>
+{
+    let dir = match path.parent()
+    {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut roms: Vec<PathBuf> = match fs::read_dir(dir)
+    {
+        Ok(entries) => entries.filter_map(|entry| entry.ok())
+                              .map(|entry| entry.path())
+                              .filter(|path| path.extension().map_or(false, |e| e.to_string_lossy().eq_ignore_ascii_case("ch8")))
+                              .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    roms.sort();
+    roms
+}
+
+// A current ROM that isn't in the list, eg - a .hex file, starts from either end
+fn adjacent_rom(roms: &[PathBuf], current: &Path, forward: bool) -> Option<PathBuf>
+{
+    if roms.is_empty()
+    {
+        return None;
+    }
+
+    let position = roms.iter().position(|rom| rom.file_name() == current.file_name());
+    let next = match (position, forward)
+    {
+        (Some(i), true) => (i + 1) % roms.len(),
+        (Some(i), false) => (i + roms.len() - 1) % roms.len(),
+        (None, true) => 0,
+        (None, false) => roms.len() - 1,
+    };
+
+    Some(roms[next].clone())
+}
+
+// eg - "roms/Space Invaders.ch8" is titled "rusty-chip — Space Invaders"
+fn title_for(path: &Path) -> String
+{
+    match path.file_stem()
+    {
+        Some(stem) => format!("rusty-chip — {}", stem.to_string_lossy()),
+        None => String::from(DEFAULT_TITLE),
+    }
+}
+
+impl Frontend for Emulator
+{
+    fn present(&mut self, video: &[bool])
+    {
+        for y in 0..machine::VIDEO_HEIGHT
+        {
+            for x in 0..machine::VIDEO_WIDTH
+            {
+                let index = y * machine::VIDEO_WIDTH + x;
+                let start = 4 * self.orientation.frame_index(x, y);
+
+                // Only the first plane exists so a pixel is palette index 0 or 1,
+                // a second XO-CHIP plane would contribute the 2 bit
+                let color = if self.fade && !video[index]
+                {
+                    blend(self.palette[0], self.palette[1], self.intensity[index])
+                }
+                else
+                {
+                    self.palette[video[index] as usize]
+                };
+                let (r, g, b) = color.to_rgb();
+
+                self.frame[start] = r;
+                self.frame[start + 1] = g;
+                self.frame[start + 2] = b;
+            }
+        }
+    }
+
+    // Keyboard and gamepad are tracked separately so releasing a key
+    // on one doesn't cancel the same key being held on the other
+    fn poll_input(&mut self) -> [bool; machine::NUM_KEYS]
+    {
+        let mut keys = self.keypad;
+        for (key, &pressed) in keys.iter_mut().zip(self.gamepad_keypad.iter())
+        {
+            *key |= pressed;
+        }
+        keys
+    }
+
+    fn beep(&mut self, on: bool)
+    {
+        self.beeping = on;
+    }
+}
+
+impl EventHandler for Emulator
+{
+    fn update(&mut self, ctx: &mut Context) -> ggez::GameResult
+    {
+        if self.retitle
+        {
+            graphics::set_window_title(ctx, &self.window_title);
+            self.retitle = false;
+        }
+
+        if self.message.is_some()
+        {
+            return Ok(());
+        }
+
+        if self.advance(timer::delta(ctx))
+        {
+            event::quit(ctx);
+        }
+
+        let beeping = self.machine.sound_timer() > 0 && !self.paused;
+        self.beep(beeping);
+        self.update_audio(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> ggez::GameResult
+    {
+        if self.resize
+        {
+            graphics::set_drawable_size(ctx, self.width, self.height)?;
+            graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, self.width, self.height))?;
+            self.resize = false;
+        }
+
+        graphics::clear(ctx, BG_COLOR);
+
+        if let Some(message) = &self.message
+        {
+            self.draw_message(ctx, message)?;
+            return graphics::present(ctx);
+        }
+
+        if self.redraw
+        {
+            self.update_buffer();
+            self.frame_image = None;
+            self.redraw = false;
+        }
+
+        self.display_buffer(ctx);
+
+        if self.show_grid
+        {
+            self.draw_grid(ctx)?;
+        }
+
+        if self.show_overlay
+        {
+            self.draw_overlay(ctx)?;
+        }
+
+        if self.show_stats
+        {
+            self.draw_stats(ctx)?;
+        }
+
+        if let Some((notice, shown)) = &self.notice
+        {
+            if shown.elapsed() < NOTICE_TIME
+            {
+                self.draw_notice(ctx, notice)?;
+            }
+            else
+            {
+                self.notice = None;
+            }
+        }
+
+        if let Some(fps) = self.target_fps
+        {
+            let frame_time = Duration::from_secs(1) / fps;
+            if let Some(remaining) = frame_time.checked_sub(self.last_present.elapsed())
+            {
+                thread::sleep(remaining);
+            }
+        }
+
+        graphics::present(ctx).expect("Error Presenting");
+        self.last_present = Instant::now();
+        self.stats.record_frame(self.machine.instruction_count(), self.last_present);
+
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: input::keyboard::KeyCode, keymods: input::keyboard::KeyMods, repeat: bool)
+    {
+        if repeat
+        {
+            return;
+        }
+
+        if keycode == TURBO_KEY
+        {
+            self.turbo = true;
+            return;
+        }
+
+        if keycode == REWIND_KEY
+        {
+            self.rewinding = true;
+            return;
+        }
+
+        if keycode == PAUSE_KEY
+        {
+            self.toggle_pause();
+            return;
+        }
+
+        if keycode == STEP_KEY
+        {
+            if keymods.contains(input::keyboard::KeyMods::SHIFT)
+            {
+                self.step_back();
+            }
+            else
+            {
+                self.step();
+            }
+            return;
+        }
+
+        if SCALE_UP_KEYS.contains(&keycode)
+        {
+            self.set_scale(self.scale.floor() + 1.0);
+            return;
+        }
+
+        if SCALE_DOWN_KEYS.contains(&keycode)
+        {
+            self.set_scale(self.scale.ceil() - 1.0);
+            return;
+        }
+
+        if keycode == OVERLAY_KEY
+        {
+            self.show_overlay = !self.show_overlay;
+            return;
+        }
+
+        if keycode == GRID_KEY
+        {
+            self.show_grid = !self.show_grid;
+            return;
+        }
+
+        if keycode == NEXT_ROM_KEY
+        {
+            self.load_next();
+            return;
+        }
+
+        if keycode == PREV_ROM_KEY
+        {
+            self.load_prev();
+            return;
+        }
+
+        if keycode == SLOWER_KEY || keycode == FASTER_KEY
+        {
+            self.step_speed(keycode == FASTER_KEY);
+            return;
+        }
+
+        if let Some(slot) = QUICKSLOT_KEYS.iter().position(|&key| key == keycode)
+        {
+            if keymods.contains(input::keyboard::KeyMods::SHIFT)
+            {
+                self.load_quickslot(slot);
+            }
+            else
+            {
+                self.save_quickslot(slot);
+            }
+            return;
+        }
+
+        if keycode == AUTOFIRE_KEY
+        {
+            self.toggle_held_autofire();
+            return;
+        }
+
+        if keycode == STATS_KEY
+        {
+            self.show_stats = !self.show_stats;
+            return;
+        }
+
+        if keycode == MUTE_KEY
+        {
+            self.toggle_mute();
+            return;
+        }
+
+        if keycode == SCREENSHOT_KEY
+        {
+            match self.screenshot()
+            {
+                Ok(path) => self.notify(format!("Saved Screenshot: {}", path)),
+                Err(e) => self.notify(format!("Error Saving Screenshot: {}", e)),
+            }
+            return;
+        }
+
+        for i in 0..machine::NUM_KEYS
+        {
+            if self.controls[i] == keycode
+            {
+                self.keypad[i] = true;
+                return;
+            }
+        }
+    }
+
+    fn key_up_event(&mut self,_ctx: &mut Context, keycode: input::keyboard::KeyCode, _keymods: input::keyboard::KeyMods)
+    {
+        if keycode == TURBO_KEY
+        {
+            self.turbo = false;
+            return;
+        }
+
+        if keycode == REWIND_KEY
+        {
+            self.rewinding = false;
+            return;
+        }
+
+        for i in 0..machine::NUM_KEYS
+        {
+            if self.controls[i] == keycode
+            {
+                self.keypad[i] = false;
+                return;
+            }
+
+        }
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool)
+    {
+        self.focus_changed(gained);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn emulator_with_rom(rom: &[u8]) -> Emulator
+    {
+        let mut machine = Chip8::new();
+        machine.load_bytes(rom).unwrap();
+
+        Emulator::new(machine, 1.0)
+    }
+
+    #[test]
+    fn timers_tick_at_60hz_independent_of_frame_size()
+    {
+        // LD VA, 60; LD DT, VA; JP 0x204
+        let mut e = emulator_with_rom(&[0x6A, 0x3C, 0xFA, 0x15, 0x12, 0x04]);
+
+        for _i in 0..20
+        {
+            e.advance(Duration::from_millis(25));
+        }
+
+        assert_eq!(e.machine.delay_timer(), 30);
+    }
+
+    #[test]
+    fn timers_drop_ticks_past_the_catch_up_limit()
+    {
+        // LD VA, 60; LD DT, VA; JP 0x204
+        let mut e = emulator_with_rom(&[0x6A, 0x3C, 0xFA, 0x15, 0x12, 0x04]);
+        e.set_max_catch_up(10);
+
+        e.advance(Duration::from_secs(1));
+
+        assert_eq!(e.machine.delay_timer(), 50);
+    }
+
+    #[test]
+    fn quickslots_restore_saved_states()
+    {
+        // ADD V0, 1; JP 0x200
+        let mut e = emulator_with_rom(&[0x70, 0x01, 0x12, 0x00]);
+        assert!(!e.load_quickslot(0));
+
+        e.machine.run_for(2).unwrap();
+        e.save_quickslot(1);
+        e.machine.run_for(4).unwrap();
+        assert_eq!(e.machine.register(0x0), 3);
+
+        assert!(e.load_quickslot(1));
+        assert_eq!(e.machine.register(0x0), 1);
+        assert!(!e.load_quickslot(NUM_QUICKSLOTS));
+    }
+
+    #[test]
+    fn speed_runs_frames_in_proportion()
+    {
+        let mut e = emulator_with_rom(&[0x12, 0x00]);
+
+        e.set_speed(0.5);
+        let half: Vec<u32> = (0..4).map(|_| e.frames_this_tick()).collect();
+        assert_eq!(half, [0, 1, 0, 1]);
+
+        e.set_speed(0.25);
+        assert_eq!((0..8).map(|_| e.frames_this_tick()).sum::<u32>(), 2);
+
+        e.step_speed(true);
+        e.step_speed(true);
+        e.step_speed(true);
+        assert_eq!(e.speed, 2.0);
+        assert_eq!(e.frames_this_tick(), 2);
+    }
+
+    #[test]
+    fn autofire_pulses_held_keys()
+    {
+        let mut e = emulator_with_rom(&[0x12, 0x00]);
+        e.keypad[0x5] = true;
+        e.keypad[0x6] = true;
+
+        e.set_autofire_interval(2);
+        e.toggle_held_autofire();
+        e.keypad[0x6] = false;
+
+        let pulses: Vec<(bool, bool)> = (0..6).map(|_|
+        {
+            let mut keys = e.poll_input();
+            e.apply_autofire(&mut keys);
+            (keys[0x5], keys[0x6])
+        }).collect();
+
+        assert_eq!(pulses, [(true, false), (true, false), (false, false), (false, false), (true, false), (true, false)]);
+        assert_eq!(e.autofire[0x6], Some(2));
+        assert_eq!(e.autofire[0x0], None);
+    }
+
+    #[test]
+    fn keys_are_polled_within_a_frame()
+    {
+        let mut e = emulator_with_rom(&[0x12, 0x00]);
+        assert!(!e.polls_mid_frame(4));
+
+        e.set_key_poll_interval(4);
+        let polls: Vec<u32> = (0..12).filter(|&i| e.polls_mid_frame(i)).collect();
+        assert_eq!(polls, [4, 8]);
+
+        e.keypad[0x3] = true;
+        e.poll_keys_mid_frame();
+        assert!(e.machine.key_state(0x3));
+
+        e.start_recording();
+        assert!(!e.polls_mid_frame(4));
+    }
+
+    #[test]
+    fn runs_headless_for_a_number_of_frames()
+    {
+        // ADD V0, 1; JP 0x200
+        let mut e = emulator_with_rom(&[0x70, 0x01, 0x12, 0x00]);
+        e.set_clock_hz(120);
+
+        e.run_headless(10).unwrap();
+        assert_eq!(e.machine.register(0x0), 10);
+
+        // EXIT
+        let mut e = emulator_with_rom(&[0x00, 0xFD]);
+        assert!(e.run_headless(10).is_ok());
+        assert!(e.machine.is_halted());
+    }
+
+    #[test]
+    fn focus_loss_only_resumes_its_own_pause()
+    {
+        let mut e = emulator_with_rom(&[0x12, 0x00]);
+
+        e.focus_changed(false);
+        assert!(e.paused);
+        e.focus_changed(true);
+        assert!(!e.paused);
+
+        e.toggle_pause();
+        e.focus_changed(false);
+        e.focus_changed(true);
+        assert!(e.paused);
+
+        e.toggle_pause();
+        e.set_pause_on_focus_loss(false);
+        e.focus_changed(false);
+        assert!(!e.paused);
+    }
+
+    #[test]
+    fn erased_pixels_fade_out()
+    {
+        // LD I, 0x050; DRW V0, V0, 1; CLS
+        let mut e = emulator_with_rom(&[0xA0, 0x50, 0xD0, 0x01, 0x00, 0xE0]);
+        e.set_fade(true);
+        e.set_fade_rate(0.5);
+
+        e.machine.cycle().unwrap();
+        e.machine.cycle().unwrap();
+        e.update_buffer();
+        assert_eq!(e.intensity[0], 1.0);
+
+        e.machine.cycle().unwrap();
+        e.update_buffer();
+        assert!(e.fading);
+        assert_eq!(e.intensity[0], 0.5);
+
+        e.update_buffer();
+        assert!(!e.fading);
+        assert_eq!(e.intensity[0], 0.0);
+    }
+
+    #[test]
+    fn frame_blend_shows_the_last_two_frames()
+    {
+        // LD I, 0x050; DRW V0, V0, 1; CLS
+        let mut e = emulator_with_rom(&[0xA0, 0x50, 0xD0, 0x01, 0x00, 0xE0]);
+        e.set_frame_blend(true);
+
+        e.machine.tick(2).unwrap();
+        assert!(e.displayed_video()[0]);
+
+        e.machine.tick(1).unwrap();
+        assert!(e.displayed_video()[0]);
+        assert!(!e.displayed_video()[0]);
+
+        e.set_frame_blend(false);
+        assert!(!e.displayed_video()[0]);
+    }
+
+    #[test]
+    fn frame_blend_clears_erased_pixels_without_another_draw()
+    {
+        // LD I, 0x050; DRW V0, V0, 1; CLS; JP 0x206
+        let mut e = emulator_with_rom(&[0xA0, 0x50, 0xD0, 0x01, 0x00, 0xE0, 0x12, 0x06]);
+        e.set_frame_blend(true);
+
+        e.machine.tick(2).unwrap();
+        e.update_buffer();
+        e.machine.tick(1).unwrap();
+        e.update_buffer();
+        assert!(e.blending);
 
+        // Nothing draws from here on, the erased pixel still clears
+        e.redraw = false;
+        e.advance(Duration::from_millis(25));
+        assert!(e.redraw);
+
+        assert!(!e.displayed_video()[0]);
+        assert!(!e.blending);
+    }
+
+    #[test]
+    fn stats_average_over_a_second()
+    {
+        let start = Instant::now();
+        let mut stats = Stats::new(start);
+
+        for frame in 1..=50
+        {
+            stats.record_frame(10 * frame, start + Duration::from_millis(20 * frame));
         }
+
+        assert!((stats.fps - 50.0).abs() < 1e-6);
+        assert!((stats.frame_ms - 20.0).abs() < 1e-6);
+        assert!((stats.ips - 500.0).abs() < 1e-6);
+
+        stats.record_frame(0, start + Duration::from_millis(1500));
+        assert_eq!(stats.frames, 1);
+    }
+
+    #[test]
+    fn titles_come_from_the_rom_file_name()
+    {
+        assert_eq!(title_for(Path::new("roms/Space Invaders.ch8")), "rusty-chip — Space Invaders");
+        assert_eq!(title_for(Path::new("PONG")), "rusty-chip — PONG");
+        assert_eq!(title_for(Path::new("")), DEFAULT_TITLE);
+    }
+
+    #[test]
+    fn orientation_turns_the_frame_and_window()
+    {
+        let mut e = emulator_with_rom(&[]);
+        e.set_orientation(Orientation::new(Rotation::Quarter, false));
+
+        assert_eq!((e.width, e.height), (32.0, 64.0));
+        assert_eq!(e.orientation.frame_index(0, 0), 31);
+        assert_eq!(e.orientation.frame_index(63, 31), 63 * 32);
+
+        assert_eq!(Orientation::new(Rotation::Half, false).frame_index(0, 0), 64 * 32 - 1);
+        assert_eq!(Orientation::new(Rotation::ThreeQuarters, false).frame_index(0, 0), 63 * 32);
+        assert_eq!(Orientation::new(Rotation::None, true).frame_index(0, 0), 63);
+    }
+
+    #[test]
+    fn adjacent_roms_wrap_around()
+    {
+        let roms: Vec<PathBuf> = ["roms/Blinky.ch8", "roms/Brix.ch8", "roms/Pong.ch8"].iter().map(PathBuf::from).collect();
+
+        assert_eq!(adjacent_rom(&roms, Path::new("roms/Brix.ch8"), true), Some(roms[2].clone()));
+        assert_eq!(adjacent_rom(&roms, Path::new("roms/Pong.ch8"), true), Some(roms[0].clone()));
+        assert_eq!(adjacent_rom(&roms, Path::new("roms/Blinky.ch8"), false), Some(roms[2].clone()));
+        assert_eq!(adjacent_rom(&roms, Path::new("roms/Tank.hex"), false), Some(roms[2].clone()));
+        assert_eq!(adjacent_rom(&[], Path::new("roms/Pong.ch8"), true), None);
+    }
+
+    #[test]
+    fn grid_lines_follow_the_scale()
+    {
+        let mut e = emulator_with_rom(&[]);
+        e.set_scale(10.0);
+
+        let lines = e.grid_lines();
+        assert_eq!(lines.len(), 63 + 31);
+        assert_eq!(lines[0], [[10.0, 0.0], [10.0, 320.0]]);
+        assert_eq!(lines[63], [[0.0, 10.0], [640.0, 10.0]]);
     }
 }
@@ -0,0 +1,113 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineError
+{
+    StackOverflow,
+    StackUnderflow,
+    InvalidAddress(u16),
+    // An instruction tried to reach past the end of memory from this address
+    MemoryOutOfBounds(u16),
+    InvalidKey(u8),
+    UnknownOpcode(u16),
+    Breakpoint(u16),
+    ConditionMet(usize, u8),
+    PcOutOfRange(u16),
+    Halted,
+}
+
+impl fmt::Display for MachineError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            MachineError::StackOverflow => write!(f, "Error Stack Overflow"),
+            MachineError::StackUnderflow => write!(f, "Error Stack Underflow"),
+            MachineError::InvalidAddress(address) => write!(f, "Error Invalid Address: {:#x}", address),
+            MachineError::MemoryOutOfBounds(address) => write!(f, "Error Memory Access Out Of Bounds: {:#x}", address),
+            MachineError::InvalidKey(key) => write!(f, "Error Invalid Key: {:#x}", key),
+            MachineError::UnknownOpcode(opcode) => write!(f, "Error Could Not Interpret Opcode: {:#06x}", opcode),
+            MachineError::Breakpoint(address) => write!(f, "Breakpoint Hit: {:#x}", address),
+            MachineError::ConditionMet(register, value) => write!(f, "Condition Met: V{:X} == {:#x}", register, value),
+            MachineError::PcOutOfRange(address) => write!(f, "Error Program Counter Left The ROM: {:#x}", address),
+            MachineError::Halted => write!(f, "Program Halted"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for MachineError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError
+{
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError
+{
+    pub fn new(line: usize, message: String) -> Self
+    {
+        AsmError { line, message }
+    }
+}
+
+impl fmt::Display for AsmError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Error On Line {}: {}", self.line, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for AsmError {}
+
+#[derive(Debug)]
+pub enum LoadError
+{
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    RomTooLarge(usize),
+    FontTooLarge(usize),
+    InvalidAddress(u16),
+    // The line number and what was wrong with the record on it
+    InvalidHex(usize, &'static str),
+}
+
+impl fmt::Display for LoadError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            #[cfg(feature = "std")]
+            LoadError::Io(e) => write!(f, "Error Reading ROM: {}", e),
+            LoadError::RomTooLarge(size) => write!(f, "Error ROM Too Large: {} bytes", size),
+            LoadError::FontTooLarge(size) => write!(f, "Error Font Too Large: {} bytes", size),
+            LoadError::InvalidAddress(address) => write!(f, "Error Invalid Load Address: {:#x}", address),
+            LoadError::InvalidHex(line, reason) => write!(f, "Error Invalid Intel HEX On Line {}: {}", line, reason),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for LoadError {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for LoadError
+{
+    fn from(e: io::Error) -> Self
+    {
+        LoadError::Io(e)
+    }
+}
@@ -0,0 +1,94 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::machine::NUM_RPL_FLAGS;
+
+// SCHIP games keep their high scores in the RPL flags, so each ROM gets
+// a file of the raw flag bytes, eg - "roms/Blinky.ch8" saves to "<dir>/Blinky.flags"
+pub fn path_for(dir: &Path, rom: &Path) -> Option<PathBuf>
+{
+    let mut name = rom.file_stem()?.to_os_string();
+    name.push(".flags");
+
+    Some(dir.join(name))
+}
+
+// $XDG_DATA_HOME/rusty-chip/flags, falling back to ~/.local/share
+pub fn default_dir() -> Option<PathBuf>
+{
+    let base = match env::var_os("XDG_DATA_HOME")
+    {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+    };
+
+    Some(base.join("rusty-chip").join("flags"))
+}
+
+// A missing or damaged file starts from zeros instead of failing
+pub fn load(path: &Path) -> [u8; NUM_RPL_FLAGS]
+{
+    let mut flags = [0; NUM_RPL_FLAGS];
+
+    if let Ok(bytes) = fs::read(path)
+    {
+        if bytes.len() == NUM_RPL_FLAGS
+        {
+            flags.copy_from_slice(&bytes);
+        }
+    }
+
+    flags
+}
+
+// Nothing is written for ROMs that never touched the flags
+pub fn save(path: &Path, flags: &[u8; NUM_RPL_FLAGS]) -> io::Result<()>
+{
+    if flags.iter().all(|&flag| flag == 0) && !path.exists()
+    {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent()
+    {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, flags)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn flags_are_named_after_the_rom()
+    {
+        assert_eq!(path_for(Path::new("saves"), Path::new("roms/Space.Fight.ch8")),
+                   Some(PathBuf::from("saves/Space.Fight.flags")));
+        assert_eq!(path_for(Path::new("saves"), Path::new("")), None);
+    }
+
+    #[test]
+    fn flags_survive_a_round_trip()
+    {
+        let dir = env::temp_dir().join(format!("rusty-chip-flags-{}", std::process::id()));
+        let path = dir.join("Blinky.flags");
+
+        assert_eq!(load(&path), [0; NUM_RPL_FLAGS]);
+
+        save(&path, &[0; NUM_RPL_FLAGS]).unwrap();
+        assert!(!path.exists());
+
+        save(&path, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(load(&path), [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        fs::write(&path, [1, 2, 3]).unwrap();
+        assert_eq!(load(&path), [0; NUM_RPL_FLAGS]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
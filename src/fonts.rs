@@ -1,6 +1,7 @@
-pub const FONT_MEMORY_START: u16 = 0x50;
+pub(crate) const FONT_MEMORY_START: u16 = 0x50;
+pub(crate) const BIG_FONT_MEMORY_START: u16 = FONT_MEMORY_START + FONTS.len() as u16;
 
-pub const FONTS: [u8; 80] =
+pub(crate) const FONTS: [u8; 80] =
 [
     // 0
     0xF0, 0x90, 0x90, 0x90, 0xF0,
@@ -35,3 +36,28 @@ pub const FONTS: [u8; 80] =
     // F
     0xF0, 0x80, 0xF0, 0x80, 0x80,
 ];
+
+// SCHIP 8x10 digits, only 0-9 exist
+pub(crate) const BIG_FONTS: [u8; 100] =
+[
+    // 0
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    // 1
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    // 2
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    // 3
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    // 4
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    // 6
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    // 7
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    // 9
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C,
+];
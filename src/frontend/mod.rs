@@ -0,0 +1,22 @@
+pub mod null;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "desktop")]
+pub mod terminal;
+
+use crate::machine::NUM_KEYS;
+
+pub trait Frontend
+{
+    fn present(&mut self, video: &[bool]);
+
+    fn poll_input(&mut self) -> [bool; NUM_KEYS];
+
+    fn beep(&mut self, on: bool);
+
+    // Chip8::run keeps going until this returns false
+    fn is_open(&self) -> bool
+    {
+        true
+    }
+}
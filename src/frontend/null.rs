@@ -0,0 +1,23 @@
+use crate::frontend::Frontend;
+use crate::machine::NUM_KEYS;
+
+// Drops everything and never presses a key, for running ROMs in
+// tests or on a server where there's no display
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullFrontend;
+
+impl Frontend for NullFrontend
+{
+    fn present(&mut self, _video: &[bool])
+    {
+    }
+
+    fn poll_input(&mut self) -> [bool; NUM_KEYS]
+    {
+        [false; NUM_KEYS]
+    }
+
+    fn beep(&mut self, _on: bool)
+    {
+    }
+}
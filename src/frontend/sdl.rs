@@ -0,0 +1,153 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::{EventPump, Sdl as SdlContext};
+
+use crate::frontend::Frontend;
+use crate::machine;
+use crate::tone::Waveform;
+
+// Scancodes rather than keycodes so the layout stays the same on
+// any keyboard, keypad index i is CONTROLS[i]
+const CONTROLS: [Scancode; machine::NUM_KEYS] =
+[
+    Scancode::Num1, Scancode::Num2, Scancode::Num3, Scancode::Num4,
+    Scancode::Q,    Scancode::W,    Scancode::E,    Scancode::R,
+    Scancode::A,    Scancode::S,    Scancode::D,    Scancode::F,
+    Scancode::Z,    Scancode::X,    Scancode::C,    Scancode::V,
+];
+
+const BEEP_HZ: f32 = 440.0;
+const VOLUME: f32 = 0.25;
+
+struct Tone
+{
+    phase: f32,
+    step: f32,
+}
+
+impl AudioCallback for Tone
+{
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32])
+    {
+        for sample in out.iter_mut()
+        {
+            *sample = VOLUME * Waveform::Square.sample(self.phase);
+            self.phase = (self.phase + self.step).fract();
+        }
+    }
+}
+
+pub struct Sdl
+{
+    // Everything else stops working once the context is dropped
+    _context: SdlContext,
+    canvas: Canvas<Window>,
+    events: EventPump,
+    tone: AudioDevice<Tone>,
+    scale: u32,
+    beeping: bool,
+    quit: bool,
+}
+
+impl Sdl
+{
+    pub fn new(scale: u32) -> Result<Sdl, String>
+    {
+        let context = sdl2::init()?;
+
+        let window = context.video()?
+                            .window("Chip-8 Emulator",
+                                    scale * machine::VIDEO_WIDTH as u32,
+                                    scale * machine::VIDEO_HEIGHT as u32)
+                            .position_centered()
+                            .build()
+                            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().present_vsync().build().map_err(|e| e.to_string())?;
+
+        let desired = AudioSpecDesired { freq: Some(44100), channels: Some(1), samples: None };
+        let tone = context.audio()?.open_playback(None, &desired, |spec|
+        {
+            Tone { phase: 0.0, step: BEEP_HZ / spec.freq as f32 }
+        })?;
+
+        let events = context.event_pump()?;
+
+        Ok(Sdl
+        {
+            _context: context,
+            canvas,
+            events,
+            tone,
+            scale,
+            beeping: false,
+            quit: false,
+        })
+    }
+}
+
+impl Frontend for Sdl
+{
+    fn present(&mut self, video: &[bool])
+    {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        for (i, _) in video.iter().enumerate().filter(|&(_, &pixel)| pixel)
+        {
+            let x = (i % machine::VIDEO_WIDTH) as u32 * self.scale;
+            let y = (i / machine::VIDEO_WIDTH) as u32 * self.scale;
+
+            // A failed rectangle only costs this frame, the next redraw repaints everything
+            let _ = self.canvas.fill_rect(Rect::new(x as i32, y as i32, self.scale, self.scale));
+        }
+
+        self.canvas.present();
+    }
+
+    fn poll_input(&mut self) -> [bool; machine::NUM_KEYS]
+    {
+        for event in self.events.poll_iter()
+        {
+            match event
+            {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => self.quit = true,
+                _ => (),
+            }
+        }
+
+        let state = self.events.keyboard_state();
+
+        let mut keys = [false; machine::NUM_KEYS];
+        for (key, &scancode) in keys.iter_mut().zip(CONTROLS.iter())
+        {
+            *key = state.is_scancode_pressed(scancode);
+        }
+        keys
+    }
+
+    fn beep(&mut self, on: bool)
+    {
+        if on && !self.beeping
+        {
+            self.tone.resume();
+        }
+        else if !on && self.beeping
+        {
+            self.tone.pause();
+        }
+        self.beeping = on;
+    }
+
+    fn is_open(&self) -> bool
+    {
+        !self.quit
+    }
+}
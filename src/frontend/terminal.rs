@@ -0,0 +1,156 @@
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::{cursor, execute, queue, style, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::frontend::Frontend;
+use crate::machine;
+
+// Same layout as the ggez controls, keypad index i is CONTROLS[i]
+const CONTROLS: [char; machine::NUM_KEYS] = ['1', '2', '3', '4',
+                                             'q', 'w', 'e', 'r',
+                                             'a', 's', 'd', 'f',
+                                             'z', 'x', 'c', 'v'];
+
+// Terminals only report key presses, never releases, so a press
+// is treated as the key being held down for a few frames
+const KEY_HOLD_FRAMES: u8 = 6;
+
+pub struct Terminal
+{
+    stdout: Stdout,
+    held: [u8; machine::NUM_KEYS],
+    beeping: bool,
+    quit: bool,
+}
+
+impl Terminal
+{
+    pub fn new() -> crossterm::Result<Terminal>
+    {
+        terminal::enable_raw_mode()?;
+
+        let mut stdout = io::stdout();
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        Ok(Terminal
+        {
+            stdout,
+            held: [0; machine::NUM_KEYS],
+            beeping: false,
+            quit: false,
+        })
+    }
+
+    fn poll_keys(&mut self) -> crossterm::Result<[bool; machine::NUM_KEYS]>
+    {
+        for frames in self.held.iter_mut()
+        {
+            *frames = frames.saturating_sub(1);
+        }
+
+        while event::poll(Duration::from_secs(0))?
+        {
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()?
+            {
+                match code
+                {
+                    KeyCode::Esc => self.quit = true,
+
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+
+                    KeyCode::Char(c) =>
+                    {
+                        if let Some(i) = CONTROLS.iter().position(|&k| k == c.to_ascii_lowercase())
+                        {
+                            self.held[i] = KEY_HOLD_FRAMES;
+                        }
+                    },
+
+                    _ => (),
+                }
+            }
+        }
+
+        let mut keys = [false; machine::NUM_KEYS];
+        for (key, &frames) in keys.iter_mut().zip(self.held.iter())
+        {
+            *key = frames > 0;
+        }
+
+        Ok(keys)
+    }
+}
+
+impl Frontend for Terminal
+{
+    // Each character cell packs two rows of pixels using half blocks
+    fn present(&mut self, video: &[bool])
+    {
+        let width = machine::VIDEO_WIDTH;
+        let height = machine::VIDEO_HEIGHT;
+
+        let mut screen = String::with_capacity((width + 2) * (height / 2) * 3);
+
+        for y in (0..height).step_by(2)
+        {
+            for x in 0..width
+            {
+                let top = video[y * width + x];
+                let bottom = y + 1 < height && video[(y + 1) * width + x];
+
+                screen.push(match (top, bottom)
+                {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            screen.push_str("\r\n");
+        }
+
+        // A failed write only costs this frame, the next redraw repaints everything
+        let _ = queue!(self.stdout, cursor::MoveTo(0, 0), style::Print(screen));
+        let _ = self.stdout.flush();
+    }
+
+    fn poll_input(&mut self) -> [bool; machine::NUM_KEYS]
+    {
+        match self.poll_keys()
+        {
+            Ok(keys) => keys,
+            Err(_) =>
+            {
+                self.quit = true;
+                [false; machine::NUM_KEYS]
+            }
+        }
+    }
+
+    // The terminal bell only rings once per beep rather than as a tone
+    fn beep(&mut self, on: bool)
+    {
+        if on && !self.beeping
+        {
+            let _ = queue!(self.stdout, style::Print('\x07'));
+            let _ = self.stdout.flush();
+        }
+        self.beeping = on;
+    }
+
+    fn is_open(&self) -> bool
+    {
+        !self.quit
+    }
+}
+
+impl Drop for Terminal
+{
+    fn drop(&mut self)
+    {
+        let _ = execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
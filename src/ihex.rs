@@ -0,0 +1,109 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::error::LoadError;
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+// Every record is ":LLAAAATT<data>CC" in hex, the byte count, address,
+// record type, data and a checksum that makes all the bytes sum to 0.
+// Only the data records are kept, as (address, bytes)
+pub(crate) fn parse(text: &str) -> Result<Vec<(usize, Vec<u8>)>, LoadError>
+{
+    let mut chunks = Vec::new();
+    let mut base = 0;
+
+    for (i, line) in text.lines().enumerate()
+    {
+        let line_number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty()
+        {
+            continue;
+        }
+
+        let bytes = decode_record(line).map_err(|reason| LoadError::InvalidHex(line_number, reason))?;
+        let count = bytes[0] as usize;
+        let address = (bytes[1] as usize) << 8 | bytes[2] as usize;
+        let kind = bytes[3];
+        let data = &bytes[4 .. bytes.len() - 1];
+
+        if data.len() != count
+        {
+            return Err(LoadError::InvalidHex(line_number, "Wrong Byte Count"));
+        }
+
+        match kind
+        {
+            DATA => chunks.push((base + address, data.to_vec())),
+            END_OF_FILE => return Ok(chunks),
+            EXTENDED_SEGMENT_ADDRESS if count == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 4,
+            EXTENDED_LINEAR_ADDRESS if count == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 16,
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => (),
+            _ => return Err(LoadError::InvalidHex(line_number, "Unsupported Record")),
+        }
+    }
+
+    Err(LoadError::InvalidHex(text.lines().count(), "Missing End Of File Record"))
+}
+
+fn decode_record(line: &str) -> Result<Vec<u8>, &'static str>
+{
+    if !line.starts_with(':')
+    {
+        return Err("Missing Start Code");
+    }
+
+    let digits = &line[1 ..];
+    if digits.len() % 2 != 0 || digits.len() < 10
+    {
+        return Err("Record Too Short");
+    }
+
+    let bytes = (0 .. digits.len()).step_by(2)
+                                   .map(|i| digits.get(i .. i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                                   .collect::<Option<Vec<u8>>>()
+                                   .ok_or("Invalid Hex Digit")?;
+
+    if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0
+    {
+        return Err("Checksum Mismatch");
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parses_data_records()
+    {
+        let text = ":04020000600561FF35\n\
+                    :020000040000FA\n\
+                    :0203000000E01B\n\
+                    :00000001FF\n";
+
+        let chunks = parse(text).unwrap();
+        assert_eq!(chunks, [(0x200, vec![0x60, 0x05, 0x61, 0xFF]), (0x300, vec![0x00, 0xE0])]);
+    }
+
+    #[test]
+    fn reports_the_bad_line()
+    {
+        assert!(matches!(parse(":04020000600561FF36\n:00000001FF"), Err(LoadError::InvalidHex(1, "Checksum Mismatch"))));
+        assert!(matches!(parse(":00000001FF\n").map(|chunks| chunks.len()), Ok(0)));
+        assert!(matches!(parse(":0402000060056134\n"), Err(LoadError::InvalidHex(1, "Wrong Byte Count"))));
+        assert!(matches!(parse(":04020000600561FF35\n"), Err(LoadError::InvalidHex(1, "Missing End Of File Record"))));
+        assert!(matches!(parse("04020000600561FF35\n"), Err(LoadError::InvalidHex(1, "Missing Start Code"))));
+        assert!(matches!(parse(":0402000060056XFF35\n"), Err(LoadError::InvalidHex(1, "Invalid Hex Digit"))));
+    }
+}
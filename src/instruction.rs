@@ -0,0 +1,162 @@
+// Register operands are indices into V0 - VF, the rest are the raw
+// values from the opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction
+{
+    Clear,
+    Return,
+    Exit,
+    Sys(u16),
+    Jump(u16),
+    Call(u16),
+    SkipEq { x: usize, kk: u8 },
+    SkipNotEq { x: usize, kk: u8 },
+    SkipEqReg { x: usize, y: usize },
+    Load { x: usize, kk: u8 },
+    Add { x: usize, kk: u8 },
+    Move { x: usize, y: usize },
+    Or { x: usize, y: usize },
+    And { x: usize, y: usize },
+    Xor { x: usize, y: usize },
+    AddReg { x: usize, y: usize },
+    Sub { x: usize, y: usize },
+    ShiftRight { x: usize, y: usize },
+    SubN { x: usize, y: usize },
+    ShiftLeft { x: usize, y: usize },
+    SkipNotEqReg { x: usize, y: usize },
+    LoadIndex(u16),
+    JumpOffset(u16),
+    Random { x: usize, kk: u8 },
+    Draw { x: usize, y: usize, n: u8 },
+    SkipKey { x: usize },
+    SkipNotKey { x: usize },
+    LoadDelay { x: usize },
+    WaitKey { x: usize },
+    SetDelay { x: usize },
+    SetSound { x: usize },
+    AddIndex { x: usize },
+    LoadFont { x: usize },
+    LoadBigFont { x: usize },
+    StoreBcd { x: usize },
+    StoreRegisters { x: usize },
+    LoadRegisters { x: usize },
+    StoreFlags { x: usize },
+    LoadFlags { x: usize },
+    Unknown(u16),
+}
+
+// To make the matching easier we can think of opcodes in general being made up of 3 parts:
+// FIRST NIBBLE - (OPTIONAL) ARGS / ADDITIONAL IDENTIFIER - ADDITIONAL IDENTIFIER
+// Eg - 00E0, 1nnn, 8xy7, Fx15
+pub fn decode(opcode: u16) -> Instruction
+{
+    use Instruction::*;
+
+    let first = (opcode & 0xF000) >> 12;
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+    let n = (opcode & 0x000F) as u8;
+    let kk = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    match first
+    {
+        0x0 => match nnn
+        {
+            0x0E0 => Clear,
+            0x0EE => Return,
+            0x0FD => Exit,
+            _ => Sys(nnn),
+        },
+
+        0x1 => Jump(nnn),
+
+        0x2 => Call(nnn),
+
+        0x3 => SkipEq { x, kk },
+
+        0x4 => SkipNotEq { x, kk },
+
+        0x5 if n == 0x0 => SkipEqReg { x, y },
+
+        0x6 => Load { x, kk },
+
+        0x7 => Add { x, kk },
+
+        0x8 => match n
+        {
+            0x0 => Move { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => AddReg { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubN { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => Unknown(opcode),
+        },
+
+        0x9 if n == 0x0 => SkipNotEqReg { x, y },
+
+        0xA => LoadIndex(nnn),
+
+        0xB => JumpOffset(nnn),
+
+        0xC => Random { x, kk },
+
+        0xD => Draw { x, y, n },
+
+        0xE => match kk
+        {
+            0x9E => SkipKey { x },
+            0xA1 => SkipNotKey { x },
+            _ => Unknown(opcode),
+        },
+
+        0xF => match kk
+        {
+            0x07 => LoadDelay { x },
+            0x0A => WaitKey { x },
+            0x15 => SetDelay { x },
+            0x18 => SetSound { x },
+            0x1E => AddIndex { x },
+            0x29 => LoadFont { x },
+            0x30 => LoadBigFont { x },
+            0x33 => StoreBcd { x },
+            0x55 => StoreRegisters { x },
+            0x65 => LoadRegisters { x },
+            0x75 => StoreFlags { x },
+            0x85 => LoadFlags { x },
+            _ => Unknown(opcode),
+        },
+
+        _ => Unknown(opcode),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn decodes_operands()
+    {
+        assert_eq!(decode(0x00E0), Instruction::Clear);
+        assert_eq!(decode(0x1228), Instruction::Jump(0x228));
+        assert_eq!(decode(0x3A10), Instruction::SkipEq { x: 0xA, kk: 0x10 });
+        assert_eq!(decode(0x8AB4), Instruction::AddReg { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xD015), Instruction::Draw { x: 0x0, y: 0x1, n: 5 });
+        assert_eq!(decode(0xF565), Instruction::LoadRegisters { x: 0x5 });
+    }
+
+    #[test]
+    fn unassigned_opcodes_are_unknown()
+    {
+        for &opcode in [0x5121, 0x8008, 0x9AB1, 0xE0FF, 0xF0FF].iter()
+        {
+            assert_eq!(decode(opcode), Instruction::Unknown(opcode));
+        }
+    }
+}
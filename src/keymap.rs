@@ -0,0 +1,141 @@
+use ggez::event::Button;
+use ggez::input::keyboard::KeyCode;
+
+use crate::machine::NUM_KEYS;
+
+pub const DEFAULT_CONTROLS: [KeyCode; NUM_KEYS] =
+[
+    KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Q,    KeyCode::W,    KeyCode::E,    KeyCode::R,
+    KeyCode::A,    KeyCode::S,    KeyCode::D,    KeyCode::F,
+    KeyCode::Z,    KeyCode::X,    KeyCode::C,    KeyCode::V,
+];
+
+// The d-pad lines up with the 2/4/6/8 directions most ROMs use and
+// the face buttons cover the common action keys
+// D-Pad Up, Down, Left, Right - 2, 8, 4, 6
+// South (A / Cross) - 5, East (B / Circle) - A
+// West (X / Square) - 0, North (Y / Triangle) - B
+// Select - E, Start - F
+pub const DEFAULT_GAMEPAD_CONTROLS: [(Button, usize); 10] =
+[
+    (Button::DPadUp, 0x2),
+    (Button::DPadDown, 0x8),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::South, 0x5),
+    (Button::East, 0xA),
+    (Button::West, 0x0),
+    (Button::North, 0xB),
+    (Button::Select, 0xE),
+    (Button::Start, 0xF),
+];
+
+// Takes 16 whitespace separated key names, the first is keypad 0
+// and so on, eg - "1 2 3 4 q w e r a s d f z x c v"
+pub fn parse_controls(mapping: &str) -> Result<[KeyCode; NUM_KEYS], String>
+{
+    let names: Vec<&str> = mapping.split_whitespace().collect();
+
+    if names.len() != NUM_KEYS
+    {
+        return Err(format!("Expected {} Keys But Found {}", NUM_KEYS, names.len()));
+    }
+
+    let mut controls = DEFAULT_CONTROLS;
+    for (control, name) in controls.iter_mut().zip(names)
+    {
+        *control = key_from_name(name).ok_or_else(|| format!("Unknown Key: {}", name))?;
+    }
+
+    Ok(controls)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode>
+{
+    let key = match name.to_uppercase().as_str()
+    {
+        "0" => KeyCode::Key0,
+        "1" => KeyCode::Key1,
+        "2" => KeyCode::Key2,
+        "3" => KeyCode::Key3,
+        "4" => KeyCode::Key4,
+        "5" => KeyCode::Key5,
+        "6" => KeyCode::Key6,
+        "7" => KeyCode::Key7,
+        "8" => KeyCode::Key8,
+        "9" => KeyCode::Key9,
+
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+
+        "NUMPAD0" => KeyCode::Numpad0,
+        "NUMPAD1" => KeyCode::Numpad1,
+        "NUMPAD2" => KeyCode::Numpad2,
+        "NUMPAD3" => KeyCode::Numpad3,
+        "NUMPAD4" => KeyCode::Numpad4,
+        "NUMPAD5" => KeyCode::Numpad5,
+        "NUMPAD6" => KeyCode::Numpad6,
+        "NUMPAD7" => KeyCode::Numpad7,
+        "NUMPAD8" => KeyCode::Numpad8,
+        "NUMPAD9" => KeyCode::Numpad9,
+
+        "UP" => KeyCode::Up,
+        "DOWN" => KeyCode::Down,
+        "LEFT" => KeyCode::Left,
+        "RIGHT" => KeyCode::Right,
+        "RETURN" | "ENTER" => KeyCode::Return,
+        "COMMA" | "," => KeyCode::Comma,
+        "PERIOD" | "." => KeyCode::Period,
+
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parses_a_full_mapping()
+    {
+        let controls = parse_controls("x 1 2 3 q w e a s d z c 4 r f v").unwrap();
+
+        assert_eq!(controls[0], KeyCode::X);
+        assert_eq!(controls[0xF], KeyCode::V);
+    }
+
+    #[test]
+    fn rejects_unknown_or_missing_keys()
+    {
+        assert!(parse_controls("1 2 3").is_err());
+        assert!(parse_controls("1 2 3 4 q w e r a s d f z x c banana").is_err());
+    }
+}
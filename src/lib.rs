@@ -0,0 +1,45 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "desktop")]
+mod builder;
+#[cfg(feature = "desktop")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod disasm;
+#[cfg(feature = "desktop")]
+mod emulator;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod flags;
+mod fonts;
+pub mod frontend;
+mod ihex;
+pub mod instruction;
+#[cfg(feature = "desktop")]
+pub mod keymap;
+pub mod machine;
+pub mod quirks;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "desktop")]
+mod rewind;
+#[cfg(feature = "std")]
+pub mod tone;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "desktop")]
+pub use builder::EmulatorBuilder;
+#[cfg(feature = "desktop")]
+pub use emulator::{Emulator, Orientation, Rotation, NUM_COLORS};
+pub use error::{AsmError, LoadError, MachineError};
+pub use instruction::{decode, Instruction};
+pub use machine::{Chip8, SaveState};
+pub use quirks::{IndexIncrement, Platform, Quirks};
+#[cfg(feature = "std")]
+pub use tone::Waveform;
@@ -1,23 +1,64 @@
-use std::fs::File;
-use std::io::Read;
-
-use rand;
-use rand::Rng;
-
-use crate::fonts::{FONT_MEMORY_START, FONTS};
+use core::fmt::{self, Write};
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, collections::{BTreeSet, VecDeque}, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::{BTreeSet, VecDeque};
+
+#[cfg(feature = "std")]
+use flate2::read::GzDecoder;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::error::{LoadError, MachineError};
+use crate::frontend::Frontend;
+use crate::ihex;
+use crate::instruction::{decode, Instruction};
+#[cfg(feature = "std")]
+use crate::quirks::Platform;
+use crate::quirks::Quirks;
+use crate::fonts::{BIG_FONT_MEMORY_START, BIG_FONTS, FONT_MEMORY_START, FONTS};
 
 pub const VIDEO_WIDTH: usize = 64;
 pub const VIDEO_HEIGHT: usize = 32;
-const VIDEO_BUFFER_SIZE: usize = VIDEO_WIDTH * VIDEO_HEIGHT;
+pub const VIDEO_BUFFER_SIZE: usize = VIDEO_WIDTH * VIDEO_HEIGHT;
 
-const ROM_MEMORY_START: u16 = 0x200;
+pub const ROM_MEMORY_START: u16 = 0x200;
+pub const ETI_660_MEMORY_START: u16 = 0x600;
 
 pub const NUM_KEYS: usize = 16;
 
+#[cfg(feature = "std")]
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const CYCLES_PER_TIMER_TICK: usize = 8;
+
+pub const NUM_RPL_FLAGS: usize = 8;
+
+pub const MEMORY_SIZE: usize = 4096;
+
+// White on black unless export_ppm_with_colors is given others
+const PPM_ON_COLOR: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const PPM_OFF_COLOR: [u8; 3] = [0x00, 0x00, 0x00];
+
+// How many instructions step_back can undo
+pub const STEP_HISTORY: usize = 64;
+
 pub struct Chip8
 {
     registers: [u8; 16],
-    memory: [u8; 4096],
+    memory: [u8; MEMORY_SIZE],
 
     program_counter: u16,
     index: u16,
@@ -28,421 +69,2297 @@ pub struct Chip8
     delay_timer: u8,
     sound_timer: u8,
 
+    rpl_flags: [u8; NUM_RPL_FLAGS],
+
     keypad: [bool; NUM_KEYS],
+    awaited_key: Option<u8>,
     video: [bool; VIDEO_BUFFER_SIZE],
     redraw: bool,
+
+    halted: bool,
+    halt_on_self_jump: bool,
+    vblank_wait: bool,
+
+    // Where the last ROM was loaded, end is exclusive
+    rom_region: Option<(u16, u16)>,
+    strict: bool,
+
+    rng: StdRng,
+    quirks: Quirks,
+
+    trace: Option<Box<dyn FnMut(u16, u16)>>,
+    sound_callback: Option<Box<dyn FnMut(bool)>>,
+    watches: Vec<(u16, Box<dyn FnMut(u16, u8, u8)>)>,
+    breakpoints: Vec<u16>,
+    opcode_breakpoints: Vec<(u16, u16)>,
+    resume_breakpoint: Option<u16>,
+    conditions: Vec<(usize, u8)>,
+
+    cheats: Vec<(u16, u8)>,
+    frozen_registers: Vec<(usize, u8)>,
+
+    instruction_count: u64,
+    opcode_histogram: [u64; 16],
+    // Only collected once enabled with set_coverage
+    coverage: Option<BTreeSet<u16>>,
+    // The state before each of the last STEP_HISTORY instructions, oldest first
+    step_history: Option<VecDeque<SaveState>>,
+}
+
+// Everything a running program can observe, the keypad is left
+// out since it belongs to whoever is providing input
+#[derive(Clone, PartialEq, Debug)]
+pub struct SaveState
+{
+    registers: [u8; 16],
+    memory: [u8; MEMORY_SIZE],
+
+    program_counter: u16,
+    index: u16,
+
+    stack: [u16; 16],
+    stack_pointer: u8,
+
+    delay_timer: u8,
+    sound_timer: u8,
+
+    rpl_flags: [u8; NUM_RPL_FLAGS],
+
+    awaited_key: Option<u8>,
+    video: [bool; VIDEO_BUFFER_SIZE],
+    halted: bool,
+    vblank_wait: bool,
+
+    rng: StdRng,
 }
 
 // Public
 impl Chip8
 {
+    // Embedded targets have no entropy source, use new_seeded there
+    #[cfg(feature = "std")]
     pub fn new() -> Self
     {
-        let mut c = Chip8
+        Chip8::with_rng(StdRng::from_entropy())
+    }
+
+    pub fn new_seeded(seed: u64) -> Self
+    {
+        Chip8::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    // Every platform shares the 64x32 display and both fonts, so only the quirks differ
+    #[cfg(feature = "std")]
+    pub fn new_for(platform: Platform) -> Self
+    {
+        let mut c = Chip8::new();
+        c.set_quirks(platform.quirks());
+        c
+    }
+
+    pub fn quirks(&self) -> Quirks
+    {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks)
+    {
+        self.quirks = quirks;
+    }
+
+    pub fn save_state(&self) -> SaveState
+    {
+        SaveState
         {
-            registers: [0; 16],
-            memory: [0; 4096],
+            registers: self.registers,
+            memory: self.memory,
 
-            program_counter: ROM_MEMORY_START,
-            index: 0,
+            program_counter: self.program_counter,
+            index: self.index,
 
-            stack: [0; 16],
-            stack_pointer: 0,
+            stack: self.stack,
+            stack_pointer: self.stack_pointer,
 
-            delay_timer: 0,
-            sound_timer: 0,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
 
-            keypad: [false; NUM_KEYS],
-            video: [false; VIDEO_BUFFER_SIZE],
-            redraw: true,
-        };
+            rpl_flags: self.rpl_flags,
 
-        c.memory[FONT_MEMORY_START as usize ..= FONT_MEMORY_START as usize + FONTS.len()].copy_from_slice(&FONTS);
+            awaited_key: self.awaited_key,
+            video: self.video,
+            halted: self.halted,
+            vblank_wait: self.vblank_wait,
 
-        c
+            rng: self.rng.clone(),
+        }
     }
 
-    pub fn load(&mut self, path: &str)
+    pub fn load_state(&mut self, state: &SaveState)
     {
-        let mut file = File::open(path).expect("Error Opening File");
-        let file_metadata = file.metadata().expect("Error Reading Metadata");
-        // Can't use array since the size of array needs to be a known constant
-        let mut buffer = vec![0; file_metadata.len() as usize];
+        self.registers = state.registers;
+        self.memory = state.memory;
 
-        file.read(&mut buffer).expect("Buffer Overflow");
+        self.program_counter = state.program_counter;
+        self.index = state.index;
 
-        for i in 0..buffer.len()
-        {
-            self.memory[ROM_MEMORY_START as usize + i] = buffer[i];
-        }
+        self.stack = state.stack;
+        self.stack_pointer = state.stack_pointer;
+
+        self.delay_timer = state.delay_timer;
+        self.set_sound_timer(state.sound_timer);
+
+        self.rpl_flags = state.rpl_flags;
+
+        self.awaited_key = state.awaited_key;
+        self.video = state.video;
+        self.redraw = true;
+        self.halted = state.halted;
+        self.vblank_wait = state.vblank_wait;
+
+        self.rng = state.rng.clone();
     }
 
-    pub fn cycle(&mut self)
+    // The small font has to end where the SCHIP big font starts
+    pub fn load_font(&mut self, font: &[u8]) -> Result<(), LoadError>
     {
-        let opcode = self.mem_read_u16();
-        self.execute(opcode);
+        let start = FONT_MEMORY_START as usize;
+        let end = start + font.len();
+
+        if end > BIG_FONT_MEMORY_START as usize
+        {
+            return Err(LoadError::FontTooLarge(font.len()));
+        }
+
+        self.memory[start .. end].copy_from_slice(font);
+        Ok(())
     }
 
-    pub fn decrement_timers(&mut self)
+    // Back to power on so another ROM can be loaded, the interpreter area
+    // keeps whichever font is loaded and the quirks and debugging hooks stay
+    pub fn reset(&mut self)
     {
-        if self.delay_timer > 0
+        self.registers = [0; 16];
+        for byte in self.memory[ROM_MEMORY_START as usize ..].iter_mut()
         {
-            self.delay_timer -= 1;
+            *byte = 0;
         }
 
-        if self.sound_timer > 0
+        self.program_counter = ROM_MEMORY_START;
+        self.index = 0;
+
+        self.stack = [0; 16];
+        self.stack_pointer = 0;
+
+        self.delay_timer = 0;
+        self.set_sound_timer(0);
+
+        self.awaited_key = None;
+        self.video = [false; VIDEO_BUFFER_SIZE];
+        self.redraw = true;
+
+        self.halted = false;
+        self.vblank_wait = false;
+        self.resume_breakpoint = None;
+        self.rom_region = None;
+
+        self.instruction_count = 0;
+        self.opcode_histogram = [0; 16];
+        if let Some(coverage) = &mut self.coverage
+        {
+            coverage.clear();
+        }
+        if let Some(history) = &mut self.step_history
         {
-            self.sound_timer -= 1;
+            history.clear();
         }
     }
 
-}
-
-
-// Private
-impl Chip8
-{
-    fn mem_read_u16(&mut self) -> u16
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, path: &str) -> Result<(), LoadError>
     {
-        let most_sig_byte = (self.memory[self.program_counter as usize] as u16) << 8;
-        let least_sig_byte = self.memory[self.program_counter as usize + 1] as u16;
+        self.load_at(path, ROM_MEMORY_START)
+    }
 
-        self.program_counter += 2;
+    // Intel HEX files carry their own addresses, so address only
+    // applies to raw binaries
+    #[cfg(feature = "std")]
+    pub fn load_at(&mut self, path: &str, address: u16) -> Result<(), LoadError>
+    {
+        let extension = Path::new(path).extension().map(|e| e.to_string_lossy().to_lowercase());
+        if let Some("hex") | Some("ihx") = extension.as_deref()
+        {
+            let text = fs::read_to_string(path)?;
+            return self.load_hex(&text);
+        }
 
-        let ret = most_sig_byte | least_sig_byte;
-        ret
+        let rom = decompress(fs::read(path)?)?;
+        self.load_bytes_at(&rom, address)
     }
 
-    fn check_keypad(&self) -> Option<u8>
+    // Nothing is written unless every record is valid, execution
+    // starts at ROM_MEMORY_START like a raw binary
+    pub fn load_hex(&mut self, text: &str) -> Result<(), LoadError>
     {
-        for i in 0..NUM_KEYS
+        let chunks = ihex::parse(text)?;
+
+        for (address, data) in &chunks
         {
-            if self.keypad[i]
+            if address + data.len() > self.memory.len()
             {
-                return Some(i as u8);
+                return Err(LoadError::InvalidAddress(*address as u16));
             }
         }
-        None
+
+        let start = chunks.iter().map(|(address, _)| *address).min().unwrap_or(0);
+        let end = chunks.iter().map(|(address, data)| address + data.len()).max().unwrap_or(0);
+
+        for (address, data) in chunks
+        {
+            self.memory[address .. address + data.len()].copy_from_slice(&data);
+        }
+        self.program_counter = ROM_MEMORY_START;
+        self.rom_region = Some((start as u16, end as u16));
+
+        Ok(())
     }
 
-    fn opcode_not_found(opcode: u16)
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError>
     {
-        panic!("Error Could Not Interpret Opcode: {:x}", opcode);
+        self.load_bytes_at(rom, ROM_MEMORY_START)
     }
 
-    // To make the matching easier we can think of opcodes in general being made up of 3 parts:
-    // FIRST NIBBLE - (OPTIONAL) ARGS / ADDITIONAL IDENTIFIER - ADDITIONAL IDENTIFIER
-    // Eg - 00E0, 1nnn, 8xy7, Fx15
-    fn execute(&mut self, opcode: u16)
+    // Copies the ROM to address and starts executing from there,
+    // eg - ETI 660 programs start at ETI_660_MEMORY_START
+    pub fn load_bytes_at(&mut self, rom: &[u8], address: u16) -> Result<(), LoadError>
     {
-        let first = ((opcode & 0xF000) >> 12) as u8;
+        let start = address as usize;
 
-        match first
+        if start >= self.memory.len()
         {
-            0x0 =>
-            {
-               let identifier = opcode & 0x000F;
-               match identifier
-               {
-                    0x0 =>
-                    {
-                        self.video = [false; VIDEO_BUFFER_SIZE];
-                    },
+            return Err(LoadError::InvalidAddress(address));
+        }
 
-                    0xE =>
-                    {
-                        self.stack_pointer -= 1;
-                        self.program_counter = self.stack[self.stack_pointer as usize];
-                    }
+        if rom.len() > self.memory.len() - start
+        {
+            return Err(LoadError::RomTooLarge(rom.len()));
+        }
 
-                    _ => Chip8::opcode_not_found(opcode),
-               }
-            },
+        self.memory[start .. start + rom.len()].copy_from_slice(rom);
+        self.program_counter = address;
+        self.rom_region = Some((address, (start + rom.len()) as u16));
 
-            0x1 =>
-            {
-                let nnn = opcode & 0x0FFF;
-                self.program_counter = nnn;
-            },
+        Ok(())
+    }
 
-            0x2 =>
-            {
-                let nnn = opcode & 0x0FFF;
-                self.stack[self.stack_pointer as usize] = self.program_counter;
-                self.stack_pointer += 1;
-                self.program_counter = nnn;
-            },
+    // With the display_wait quirk a cycle after a draw does nothing until vblank
+    pub fn cycle(&mut self) -> Result<bool, MachineError>
+    {
+        if self.vblank_wait
+        {
+            return Ok(false);
+        }
 
-            0x3 =>
-            {
-                let kk = (opcode & 0x00FF) as u8;
-                let x = ((opcode & 0x0F00) >> 8) as usize;
+        let pending = self.redraw;
+        self.redraw = false;
 
-                if self.registers[x] == kk
-                {
-                    self.program_counter += 2;
-                }
-            },
+        let result = self.step();
 
-            0x4 =>
-            {
-                let kk = (opcode & 0x00FF) as u8;
-                let x = ((opcode & 0x0F00) >> 8) as usize;
+        let changed = self.redraw;
+        self.redraw |= pending;
+        result.map(|_| changed)
+    }
 
-                if self.registers[x] != kk
-                {
-                    self.program_counter += 2;
-                }
-            },
+    // On error the program counter is left pointing at the faulting instruction,
+    // except for ConditionMet which is only known once the instruction has run
+    pub fn step(&mut self) -> Result<u16, MachineError>
+    {
+        if self.halted
+        {
+            return Err(MachineError::Halted);
+        }
 
-            0x5 =>
-            {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
+        let address = self.program_counter;
 
-                if self.registers[x] == self.registers[y]
-                {
-                    self.program_counter += 2;
-                }
-            },
+        if self.strict && !self.pc_in_range(address)
+        {
+            return Err(MachineError::PcOutOfRange(address));
+        }
 
-            0x6 =>
-            {
-                let kk = (opcode & 0x00FF) as u8;
-                let x = ((opcode & 0x0F00) >> 8) as usize;
+        // Stepping again after a breakpoint runs the instruction it stopped at
+        if self.resume_breakpoint.take() != Some(address)
+            && (self.breakpoints.contains(&address) || self.breaks_on_opcode(address))
+        {
+            self.resume_breakpoint = Some(address);
+            return Err(MachineError::Breakpoint(address));
+        }
 
-                self.registers[x] = kk;
-            },
+        if address as usize + 1 >= MEMORY_SIZE
+        {
+            return Err(MachineError::MemoryOutOfBounds(address));
+        }
 
-            0x7 =>
-            {
-                let kk = (opcode & 0x00FF) as u8;
-                let x = ((opcode & 0x0F00) >> 8) as usize;
+        self.record_step();
+        let opcode = self.mem_read_u16();
 
-                self.registers[x] = self.registers[x].wrapping_add(kk);
-            },
+        if let Some(trace) = &mut self.trace
+        {
+            trace(address, opcode);
+        }
 
-            0x8 =>
+        let registers = self.registers;
+
+        if let Err(e) = self.execute(opcode)
+        {
+            self.program_counter = address;
+            if let Some(history) = &mut self.step_history
             {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                let identifier = opcode & 0x000F;
+                history.pop_back();
+            }
+            return Err(e);
+        }
 
-                match identifier
-                {
-                    0x0 => self.registers[x] = self.registers[y],
+        for &(x, value) in &self.frozen_registers
+        {
+            self.registers[x] = value;
+        }
 
-                    0x1 => self.registers[x] |= self.registers[y],
+        self.instruction_count += 1;
+        self.opcode_histogram[(opcode >> 12) as usize] += 1;
+        if let Some(coverage) = &mut self.coverage
+        {
+            coverage.insert(opcode);
+        }
 
-                    0x2 => self.registers[x] &= self.registers[y],
+        // Only fires when the register changes to the value, not while it stays there
+        for &(x, value) in &self.conditions
+        {
+            if self.registers[x] == value && registers[x] != value
+            {
+                return Err(MachineError::ConditionMet(x, value));
+            }
+        }
 
-                    0x3 => self.registers[x] ^= self.registers[y],
+        Ok(opcode)
+    }
 
-                    0x4 =>
-                    {
-                        let (sum, carry) = self.registers[x].overflowing_add(self.registers[y]);
+    // Exactly n instructions with no timers, vblank or input in between,
+    // for tests that check the machine one instruction at a time
+    pub fn tick(&mut self, n: usize) -> Result<(), MachineError>
+    {
+        for _i in 0..n
+        {
+            self.step()?;
+        }
 
-                        if carry
-                        {
-                            self.registers[0xF] = 1;
-                        }
-                        else
-                        {
-                            self.registers[0xF] = 0;
-                        }
+        Ok(())
+    }
 
-                        self.registers[x] = sum;
-                    },
+    // Approximates the frontend's default timing of 8 cycles per 60Hz timer tick
+    pub fn run_for(&mut self, cycles: usize) -> Result<(), MachineError>
+    {
+        for i in 1 ..= cycles
+        {
+            self.cycle()?;
 
-                    0x5 =>
-                    {
-                        let (diff, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
+            if i % CYCLES_PER_TIMER_TICK == 0
+            {
+                self.decrement_timers();
+                self.vblank();
+            }
+        }
 
-                        if borrow
-                        {
-                            self.registers[0xF] = 0;
-                        }
-                        else
-                        {
-                            self.registers[0xF] = 1;
-                        }
+        Ok(())
+    }
 
-                        self.registers[x] = diff;
-                    },
+    // One 60Hz frame without any frontend, returns whether the video changed
+    pub fn advance_frame(&mut self, cycles: u32) -> Result<bool, MachineError>
+    {
+        self.apply_cheats();
 
-                    0x6 =>
-                    {
-                        self.registers[0xF] = self.registers[x] & 0x1;
-                        self.registers[x] >>= 1;
-                    },
+        let mut redraw = false;
+        for _i in 0..cycles
+        {
+            redraw |= self.cycle()?;
 
-                    0x7 =>
-                    {
-                        let (diff, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
+            if self.vblank_wait
+            {
+                break;
+            }
+        }
+        self.decrement_timers();
+        self.vblank();
 
-                        if borrow
-                        {
-                            self.registers[0xF] = 0;
-                        }
-                        else
-                        {
-                            self.registers[0xF] = 1;
-                        }
+        Ok(redraw)
+    }
 
-                        self.registers[x] = diff;
-                    },
+    pub fn run_frame<F: Frontend>(&mut self, frontend: &mut F, cycles: u32) -> Result<(), MachineError>
+    {
+        self.keypad = frontend.poll_input();
 
-                    0xE =>
-                    {
-                        self.registers[0xF] = (self.registers[x] >> 7) & 1;
-                        self.registers[x] <<= 1;
-                    }
+        if self.advance_frame(cycles)?
+        {
+            frontend.present(&self.video);
+        }
+        frontend.beep(self.sound_timer > 0);
 
-                    _ => Chip8::opcode_not_found(opcode),
-                }
-            },
+        Ok(())
+    }
 
-            0x9 =>
-            {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
+    #[cfg(feature = "std")]
+    pub fn run<F: Frontend>(&mut self, frontend: &mut F, cycles_per_frame: u32) -> Result<(), MachineError>
+    {
+        let frame_time = Duration::from_secs(1) / 60;
 
-                if self.registers[x] != self.registers[y]
-                {
-                    self.program_counter += 2;
-                }
-            },
+        frontend.present(&self.video);
 
-            0xA => self.index = opcode & 0x0FFF,
+        while frontend.is_open() && !self.halted
+        {
+            let start = Instant::now();
 
-            0xB => self.program_counter = (opcode & 0x0FFF) + (self.registers[0] as u16),
+            self.run_frame(frontend, cycles_per_frame)?;
 
-            0xC =>
+            if let Some(remaining) = frame_time.checked_sub(start.elapsed())
             {
-                let ran_byte: u8 = rand::thread_rng().gen();
+                thread::sleep(remaining);
+            }
+        }
 
-                let kk = (opcode & 0x00FF) as u8;
-                let x = ((opcode & 0x0F00) >> 8) as usize;
+        Ok(())
+    }
 
-                self.registers[x] = ran_byte & kk;
-            },
+    // Set by 00FD, after which step only returns MachineError::Halted
+    pub fn is_halted(&self) -> bool
+    {
+        self.halted
+    }
 
-            0xD =>
-            {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                let n = (opcode & 0x000F) as usize;
+    // Many ROMs finish by jumping to the same address forever,
+    // optionally treat that the same as 00FD
+    pub fn set_halt_on_self_jump(&mut self, enabled: bool)
+    {
+        self.halt_on_self_jump = enabled;
+    }
 
-                let x = self.registers[x];
-                let y = self.registers[y];
+    // Running off the end of the ROM is reported as PcOutOfRange instead
+    // of executing whatever data or empty memory follows it
+    pub fn set_strict(&mut self, strict: bool)
+    {
+        self.strict = strict;
+    }
 
-                let mut collision = false;
+    pub fn set_trace(&mut self, f: Box<dyn FnMut(u16, u16)>)
+    {
+        self.trace = Some(f);
+    }
 
-                for i in 0..n
-                {
-                    let row_of_sprite = self.memory[self.index as usize + i];
-                    for j in 0..8
-                    {
-                        let pixel = (row_of_sprite & (0x80 >> j)) != 0;
-                        let pixel_y = (y as usize + i) % VIDEO_HEIGHT;
-                        let pixel_x = (x as usize + j) % VIDEO_HEIGHT;
+    pub fn clear_trace(&mut self)
+    {
+        self.trace = None;
+    }
 
-                        let video_pixel = &mut self.video[pixel_y * VIDEO_WIDTH + pixel_x];
+    // Called with true when the sound timer starts and false when it runs
+    // out, so a frontend can start and stop its tone without polling
+    pub fn set_sound_callback(&mut self, f: Box<dyn FnMut(bool)>)
+    {
+        self.sound_callback = Some(f);
+    }
 
-                        if pixel
-                        {
-                            collision |= *video_pixel;
-                            *video_pixel ^= true;
-                            self.redraw = true;
-                        }
-                    }
-                }
+    pub fn clear_sound_callback(&mut self)
+    {
+        self.sound_callback = None;
+    }
 
-                if collision
-                {
-                    self.registers[0xF] = 1;
-                }
-                else
-                {
-                    self.registers[0xF] = 0;
-                }
-            },
+    // Called with (address, old, new) whenever an instruction writes the
+    // address, several watches on the same address all get called
+    pub fn watch_memory(&mut self, address: u16, f: Box<dyn FnMut(u16, u8, u8)>)
+    {
+        self.watches.push((address, f));
+    }
+
+    pub fn clear_watches(&mut self)
+    {
+        self.watches.clear();
+    }
+
+    // step stops with MachineError::Breakpoint before running the instruction
+    pub fn add_breakpoint(&mut self, address: u16)
+    {
+        if !self.breakpoints.contains(&address)
+        {
+            self.breakpoints.push(address);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16)
+    {
+        self.breakpoints.retain(|&breakpoint| breakpoint != address);
+        self.resume_breakpoint = None;
+    }
+
+    pub fn breakpoints(&self) -> &[u16]
+    {
+        &self.breakpoints
+    }
+
+    // Stops like a breakpoint before any instruction where opcode & mask == pattern,
+    // eg - mask 0xF000 and pattern 0xD000 breaks on every DRW
+    pub fn break_on_opcode_mask(&mut self, mask: u16, pattern: u16)
+    {
+        if !self.opcode_breakpoints.contains(&(mask, pattern))
+        {
+            self.opcode_breakpoints.push((mask, pattern));
+        }
+    }
+
+    pub fn clear_opcode_breakpoints(&mut self)
+    {
+        self.opcode_breakpoints.clear();
+        self.resume_breakpoint = None;
+    }
+
+    // step returns MachineError::ConditionMet once any condition becomes true
+    pub fn add_watch_condition(&mut self, register: usize, value: u8)
+    {
+        if register < self.registers.len() && !self.conditions.contains(&(register, value))
+        {
+            self.conditions.push((register, value));
+        }
+    }
+
+    pub fn clear_watch_conditions(&mut self)
+    {
+        self.conditions.clear();
+    }
+
+    // Return addresses of the calls currently in progress, innermost last
+    pub fn call_stack(&self) -> &[u16]
+    {
+        &self.stack[.. self.stack_pointer as usize]
+    }
+
+    pub fn stack_depth(&self) -> u8
+    {
+        self.stack_pointer
+    }
+
+    pub fn instruction_count(&self) -> u64
+    {
+        self.instruction_count
+    }
+
+    // Executed instructions counted by the first nibble of their opcode
+    pub fn opcode_histogram(&self) -> &[u64; 16]
+    {
+        &self.opcode_histogram
+    }
+
+    // Turning coverage off throws away what was collected
+    pub fn set_coverage(&mut self, enabled: bool)
+    {
+        if !enabled
+        {
+            self.coverage = None;
+        }
+        else if self.coverage.is_none()
+        {
+            self.coverage = Some(BTreeSet::new());
+        }
+    }
 
-            0xE =>
+    // Keeps a save state from before every instruction so step_back can
+    // undo them, turning it off throws the history away
+    pub fn set_step_history(&mut self, enabled: bool)
+    {
+        if !enabled
+        {
+            self.step_history = None;
+        }
+        else if self.step_history.is_none()
+        {
+            self.step_history = Some(VecDeque::with_capacity(STEP_HISTORY));
+        }
+    }
+
+    // Undoes the last instruction, false once the history runs out
+    pub fn step_back(&mut self) -> bool
+    {
+        match self.step_history.as_mut().and_then(VecDeque::pop_back)
+        {
+            Some(state) =>
             {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let identifier = opcode & 0x00FF;
+                self.load_state(&state);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Every distinct opcode executed so far in ascending order
+    pub fn coverage(&self) -> Vec<u16>
+    {
+        self.coverage.iter().flatten().copied().collect()
+    }
+
+    pub fn delay_timer(&self) -> u8
+    {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8
+    {
+        self.sound_timer
+    }
+
+    pub fn is_sound_active(&self) -> bool
+    {
+        self.sound_timer > 0
+    }
+
+    pub fn rpl_flags(&self) -> &[u8; NUM_RPL_FLAGS]
+    {
+        &self.rpl_flags
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; NUM_RPL_FLAGS])
+    {
+        self.rpl_flags = flags;
+    }
+
+    pub fn memory(&self) -> &[u8]
+    {
+        &self.memory
+    }
+
+    // A copy to compare against later with diff_memory
+    pub fn snapshot_memory(&self) -> [u8; MEMORY_SIZE]
+    {
+        self.memory
+    }
+
+    // Out of range addresses read as 0 and writes to them are dropped
+    pub fn peek(&self, address: u16) -> u8
+    {
+        self.memory.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn poke(&mut self, address: u16, value: u8)
+    {
+        if let Some(cell) = self.memory.get_mut(address as usize)
+        {
+            *cell = value;
+        }
+    }
+
+    pub fn find_byte(&self, value: u8) -> Vec<u16>
+    {
+        self.find_byte_in(value, 0, self.memory.len() as u16)
+    }
+
+    // Searches from start up to but not including end
+    pub fn find_byte_in(&self, value: u8, start: u16, end: u16) -> Vec<u16>
+    {
+        let end = (end as usize).min(self.memory.len());
+        let start = (start as usize).min(end);
+
+        self.memory[start .. end].iter()
+                                 .enumerate()
+                                 .filter(|&(_, &byte)| byte == value)
+                                 .map(|(i, _)| (start + i) as u16)
+                                 .collect()
+    }
+
+    pub fn add_cheat(&mut self, address: u16, value: u8) -> Result<(), MachineError>
+    {
+        if address as usize >= self.memory.len()
+        {
+            return Err(MachineError::InvalidAddress(address));
+        }
+
+        self.cheats.retain(|&(a, _)| a != address);
+        self.cheats.push((address, value));
+
+        Ok(())
+    }
+
+    pub fn clear_cheats(&mut self)
+    {
+        self.cheats.clear();
+    }
+
+    pub fn apply_cheats(&mut self)
+    {
+        for &(address, value) in &self.cheats
+        {
+            self.memory[address as usize] = value;
+        }
+    }
+
+    // Unlike a cheat this pins a register rather than memory, it is
+    // written back after every instruction so nothing can change it
+    pub fn freeze_register(&mut self, x: usize, value: u8)
+    {
+        if x < self.registers.len()
+        {
+            self.frozen_registers.retain(|&(frozen, _)| frozen != x);
+            self.frozen_registers.push((x, value));
+            self.registers[x] = value;
+        }
+    }
+
+    pub fn unfreeze_register(&mut self, x: usize)
+    {
+        self.frozen_registers.retain(|&(frozen, _)| frozen != x);
+    }
+
+    pub fn video_buffer(&self) -> &[bool]
+    {
+        &self.video
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool
+    {
+        if x >= VIDEO_WIDTH || y >= VIDEO_HEIGHT
+        {
+            return false;
+        }
+
+        self.video[y * VIDEO_WIDTH + x]
+    }
+
+    pub fn registers(&self) -> &[u8; 16]
+    {
+        &self.registers
+    }
+
+    pub fn register(&self, x: usize) -> u8
+    {
+        self.registers.get(x).copied().unwrap_or(0)
+    }
+
+    pub fn program_counter(&self) -> u16
+    {
+        self.program_counter
+    }
+
+    pub fn index(&self) -> u16
+    {
+        self.index
+    }
+
+    pub fn stack_pointer(&self) -> u8
+    {
+        self.stack_pointer
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool)
+    {
+        if key < NUM_KEYS
+        {
+            self.keypad[key] = pressed;
+        }
+    }
+
+    pub fn key_state(&self, key: usize) -> bool
+    {
+        key < NUM_KEYS && self.keypad[key]
+    }
+
+    pub fn redraw(&self) -> bool
+    {
+        self.redraw
+    }
+
+    pub fn clear_redraw(&mut self)
+    {
+        self.redraw = false;
+    }
+
+    pub fn screen_ascii(&self) -> String
+    {
+        let mut screen = String::with_capacity((VIDEO_WIDTH + 1) * VIDEO_HEIGHT);
 
-                match identifier
+        for y in 0..VIDEO_HEIGHT
+        {
+            for x in 0..VIDEO_WIDTH
+            {
+                screen.push(match self.pixel(x, y)
                 {
-                    0x9E =>
-                    {
-                        if self.keypad[self.registers[x] as usize]
-                        {
-                            self.program_counter += 2;
-                        }
-                    },
+                    true => '#',
+                    false => ' ',
+                });
+            }
+            screen.push('\n');
+        }
 
-                    0xA1 =>
-                    {
-                        if !self.keypad[self.registers[x] as usize]
-                        {
-                            self.program_counter += 2;
-                        }
-                    },
+        screen
+    }
 
-                    _ => Chip8::opcode_not_found(opcode),
-                }
-            },
+    // A binary P6 image, each pixel becomes a scale x scale square
+    pub fn export_ppm(&self, scale: usize) -> Vec<u8>
+    {
+        self.export_ppm_with_colors(scale, PPM_ON_COLOR, PPM_OFF_COLOR)
+    }
+
+    pub fn export_ppm_with_colors(&self, scale: usize, on: [u8; 3], off: [u8; 3]) -> Vec<u8>
+    {
+        let scale = scale.max(1);
+        let width = VIDEO_WIDTH * scale;
+        let height = VIDEO_HEIGHT * scale;
 
-            0xF =>
+        let mut header = String::new();
+        write!(header, "P6\n{} {}\n255\n", width, height).expect("Error Writing PPM Header");
+
+        let mut ppm = Vec::with_capacity(header.len() + 3 * width * height);
+        ppm.extend_from_slice(header.as_bytes());
+
+        for y in 0..height
+        {
+            for x in 0..width
             {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let identifier = opcode & 0x00FF;
+                let color = match self.pixel(x / scale, y / scale)
+                {
+                    true => on,
+                    false => off,
+                };
+                ppm.extend_from_slice(&color);
+            }
+        }
+
+        ppm
+    }
 
-                match identifier
+    // Packs two rows into each line of text
+    pub fn screen_half_blocks(&self) -> String
+    {
+        let mut screen = String::with_capacity((3 * VIDEO_WIDTH + 1) * VIDEO_HEIGHT / 2);
+
+        for y in (0..VIDEO_HEIGHT).step_by(2)
+        {
+            for x in 0..VIDEO_WIDTH
+            {
+                screen.push(match (self.pixel(x, y), self.pixel(x, y + 1))
                 {
-                    0x07 => self.registers[x] = self.delay_timer,
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            screen.push('\n');
+        }
+
+        screen
+    }
+
+    // Marks the start of a new 60Hz frame for the display_wait quirk
+    pub fn vblank(&mut self)
+    {
+        self.vblank_wait = false;
+    }
+
+    pub fn is_waiting_for_vblank(&self) -> bool
+    {
+        self.vblank_wait
+    }
+
+    pub fn decrement_timers(&mut self)
+    {
+        if self.delay_timer > 0
+        {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0
+        {
+            self.set_sound_timer(self.sound_timer - 1);
+        }
+    }
+
+}
 
-                    0x0A =>
-                    {
-                        match self.check_keypad()
-                        {
-                            Some(key) => self.registers[x] = key,
-                            None => self.program_counter -= 2,
-                        }
-                    },
 
-                    0x15 => self.delay_timer = self.registers[x],
+// Private
+impl Chip8
+{
+    fn with_rng(rng: StdRng) -> Self
+    {
+        let mut c = Chip8
+        {
+            registers: [0; 16],
+            memory: [0; MEMORY_SIZE],
 
-                    0x18 => self.sound_timer = self.registers[x],
+            program_counter: ROM_MEMORY_START,
+            index: 0,
 
-                    0x1E => self.index = self.index.wrapping_add(self.registers[x] as u16),
+            stack: [0; 16],
+            stack_pointer: 0,
 
-                    0x29 => self.index = FONT_MEMORY_START + (5 * self.registers[x] as u16),
+            delay_timer: 0,
+            sound_timer: 0,
 
-                    0x33 =>
-                    {
-                        let mut value = self.registers[x];
+            rpl_flags: [0; NUM_RPL_FLAGS],
 
-                        self.memory[self.index as usize + 2] = value % 10;
-                        value /= 10;
+            keypad: [false; NUM_KEYS],
+            awaited_key: None,
+            video: [false; VIDEO_BUFFER_SIZE],
+            redraw: true,
 
-                        self.memory[self.index as usize + 1] = value % 10;
-                        value /= 10;
+            halted: false,
+            halt_on_self_jump: false,
+            vblank_wait: false,
 
-                        self.memory[self.index as usize] = value % 10;
-                    }
+            rom_region: None,
+            strict: false,
 
-                    0x55 => self.memory[self.index as usize ..= self.index as usize + x].copy_from_slice(&self.registers[0 ..= x]),
+            rng,
+            quirks: Quirks::default(),
 
-                    0x65 => self.registers[0 ..= x].copy_from_slice(&self.memory[self.index as usize ..= self.index as usize + x]),
+            trace: None,
+            sound_callback: None,
+            watches: Vec::new(),
+            breakpoints: Vec::new(),
+            opcode_breakpoints: Vec::new(),
+            resume_breakpoint: None,
+            conditions: Vec::new(),
 
-                    _ => Chip8::opcode_not_found(opcode),
-                }
-            }
+            cheats: Vec::new(),
+            frozen_registers: Vec::new(),
 
-            _ => Chip8::opcode_not_found(opcode),
+            instruction_count: 0,
+            opcode_histogram: [0; 16],
+            coverage: None,
+            step_history: None,
+        };
+
+        let font_start = FONT_MEMORY_START as usize;
+        c.memory[font_start .. font_start + FONTS.len()].copy_from_slice(&FONTS);
+
+        let big_font_start = BIG_FONT_MEMORY_START as usize;
+        c.memory[big_font_start .. big_font_start + BIG_FONTS.len()].copy_from_slice(&BIG_FONTS);
+
+        c
+    }
+
+    fn breaks_on_opcode(&self, address: u16) -> bool
+    {
+        if self.opcode_breakpoints.is_empty()
+        {
+            return false;
+        }
+
+        let opcode = (self.peek(address) as u16) << 8 | self.peek(address.wrapping_add(1)) as u16;
+        self.opcode_breakpoints.iter().any(|&(mask, pattern)| opcode & mask == pattern)
+    }
+
+    fn record_step(&mut self)
+    {
+        if self.step_history.is_none()
+        {
+            return;
+        }
+
+        let state = self.save_state();
+        if let Some(history) = &mut self.step_history
+        {
+            if history.len() == STEP_HISTORY
+            {
+                history.pop_front();
+            }
+            history.push_back(state);
+        }
+    }
+
+    fn set_sound_timer(&mut self, value: u8)
+    {
+        let was_active = self.sound_timer > 0;
+        self.sound_timer = value;
+
+        if let (true, Some(callback)) = (was_active != (value > 0), &mut self.sound_callback)
+        {
+            callback(value > 0);
+        }
+    }
+
+    // Before any ROM is loaded only the end of memory is checked
+    fn pc_in_range(&self, address: u16) -> bool
+    {
+        if address as usize > MEMORY_SIZE - 2
+        {
+            return false;
+        }
+
+        match self.rom_region
+        {
+            Some((start, end)) => address >= start && address < end,
+            None => true,
+        }
+    }
+
+    fn mem_read_u16(&mut self) -> u16
+    {
+        let most_sig_byte = (self.memory[self.program_counter as usize] as u16) << 8;
+        let least_sig_byte = self.memory[self.program_counter as usize + 1] as u16;
+
+        self.program_counter += 2;
+
+        let ret = most_sig_byte | least_sig_byte;
+        ret
+    }
+
+    fn mem_write(&mut self, address: usize, value: u8)
+    {
+        let old = self.memory[address];
+        self.memory[address] = value;
+
+        for (watched, f) in self.watches.iter_mut()
+        {
+            if *watched as usize == address
+            {
+                f(*watched, old, value);
+            }
+        }
+    }
+
+    // The whole range is checked before anything is read or written, so an
+    // instruction running off the end of memory changes nothing
+    fn memory_range(&self, start: usize, len: usize) -> Result<&[u8], MachineError>
+    {
+        self.memory.get(start .. start + len).ok_or(MachineError::MemoryOutOfBounds(start as u16))
+    }
+
+    fn key_pressed(&self, key: u8) -> Result<bool, MachineError>
+    {
+        self.keypad.get(key as usize).copied().ok_or(MachineError::InvalidKey(key))
+    }
+
+    fn reset_vf_on_logic(&mut self)
+    {
+        if self.quirks.reset_vf_on_logic
+        {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    fn check_keypad(&self) -> Option<u8>
+    {
+        for i in 0..NUM_KEYS
+        {
+            if self.keypad[i]
+            {
+                return Some(i as u8);
+            }
+        }
+        None
+    }
+
+    fn execute(&mut self, opcode: u16) -> Result<(), MachineError>
+    {
+        match decode(opcode)
+        {
+            Instruction::Clear =>
+            {
+                self.video = [false; VIDEO_BUFFER_SIZE];
+                self.redraw = true;
+            },
+
+            Instruction::Return =>
+            {
+                if self.stack_pointer == 0
+                {
+                    return Err(MachineError::StackUnderflow);
+                }
+
+                self.stack_pointer -= 1;
+                self.program_counter = self.stack[self.stack_pointer as usize];
+            },
+
+            // Machine code routines only existed on the original hardware
+            Instruction::Sys(_) => (),
+
+            Instruction::Exit => self.halted = true,
+
+            Instruction::Jump(nnn) =>
+            {
+                if self.halt_on_self_jump && nnn == self.program_counter - 2
+                {
+                    self.halted = true;
+                }
+
+                self.program_counter = nnn;
+            },
+
+            Instruction::Call(nnn) =>
+            {
+                if self.stack_pointer as usize == self.stack.len()
+                {
+                    return Err(MachineError::StackOverflow);
+                }
+
+                self.stack[self.stack_pointer as usize] = self.program_counter;
+                self.stack_pointer += 1;
+                self.program_counter = nnn;
+            },
+
+            Instruction::SkipEq { x, kk } =>
+            {
+                if self.registers[x] == kk
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::SkipNotEq { x, kk } =>
+            {
+                if self.registers[x] != kk
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::SkipEqReg { x, y } =>
+            {
+                if self.registers[x] == self.registers[y]
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::Load { x, kk } => self.registers[x] = kk,
+
+            Instruction::Add { x, kk } => self.registers[x] = self.registers[x].wrapping_add(kk),
+
+            Instruction::Move { x, y } => self.registers[x] = self.registers[y],
+
+            Instruction::Or { x, y } =>
+            {
+                self.registers[x] |= self.registers[y];
+                self.reset_vf_on_logic();
+            },
+
+            Instruction::And { x, y } =>
+            {
+                self.registers[x] &= self.registers[y];
+                self.reset_vf_on_logic();
+            },
+
+            Instruction::Xor { x, y } =>
+            {
+                self.registers[x] ^= self.registers[y];
+                self.reset_vf_on_logic();
+            },
+
+            // The flag is written last so it wins when x is F
+            Instruction::AddReg { x, y } =>
+            {
+                let (sum, carry) = self.registers[x].overflowing_add(self.registers[y]);
+                self.registers[x] = sum;
+
+                if carry
+                {
+                    self.registers[0xF] = 1;
+                }
+                else
+                {
+                    self.registers[0xF] = 0;
+                }
+            },
+
+            Instruction::Sub { x, y } =>
+            {
+                let (diff, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
+                self.registers[x] = diff;
+
+                if borrow
+                {
+                    self.registers[0xF] = 0;
+                }
+                else
+                {
+                    self.registers[0xF] = 1;
+                }
+            },
+
+            Instruction::ShiftRight { x, .. } =>
+            {
+                let flag = self.registers[x] & 0x1;
+                self.registers[x] >>= 1;
+                self.registers[0xF] = flag;
+            },
+
+            Instruction::SubN { x, y } =>
+            {
+                let (diff, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
+                self.registers[x] = diff;
+
+                if borrow
+                {
+                    self.registers[0xF] = 0;
+                }
+                else
+                {
+                    self.registers[0xF] = 1;
+                }
+            },
+
+            Instruction::ShiftLeft { x, .. } =>
+            {
+                let flag = (self.registers[x] >> 7) & 1;
+                self.registers[x] <<= 1;
+                self.registers[0xF] = flag;
+            },
+
+            Instruction::SkipNotEqReg { x, y } =>
+            {
+                if self.registers[x] != self.registers[y]
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::LoadIndex(nnn) => self.index = nnn,
+
+            Instruction::JumpOffset(nnn) =>
+            {
+                let x = if self.quirks.jump_uses_vx
+                {
+                    ((nnn >> 8) & 0xF) as usize
+                }
+                else
+                {
+                    0
+                };
+                self.program_counter = nnn + (self.registers[x] as u16);
+            },
+
+            Instruction::Random { x, kk } =>
+            {
+                let ran_byte: u8 = self.rng.gen();
+                self.registers[x] = ran_byte & kk;
+            },
+
+            Instruction::Draw { x, y, n } =>
+            {
+                let x = self.registers[x] as usize % VIDEO_WIDTH;
+                let y = self.registers[y] as usize % VIDEO_HEIGHT;
+
+                // SCHIP uses n = 0 for a 16x16 sprite made of two bytes per row
+                let (rows, width) = match n
+                {
+                    0 => (16, 16),
+                    _ => (n as usize, 8),
+                };
+
+                let bytes_per_row = width / 8;
+                let mut sprite = [0; 32];
+                sprite[.. rows * bytes_per_row].copy_from_slice(self.memory_range(self.index as usize, rows * bytes_per_row)?);
+
+                let mut collision = false;
+
+                for i in 0..rows
+                {
+                    let row_of_sprite = if width == 16
+                    {
+                        (sprite[2 * i] as u16) << 8 | sprite[2 * i + 1] as u16
+                    }
+                    else
+                    {
+                        (sprite[i] as u16) << 8
+                    };
+
+                    if !self.quirks.wrap_sprites && y + i >= VIDEO_HEIGHT
+                    {
+                        break;
+                    }
+
+                    for j in 0..width
+                    {
+                        if !self.quirks.wrap_sprites && x + j >= VIDEO_WIDTH
+                        {
+                            break;
+                        }
+
+                        let pixel = (row_of_sprite & (0x8000 >> j)) != 0;
+                        let pixel_y = (y + i) % VIDEO_HEIGHT;
+                        let pixel_x = (x + j) % VIDEO_WIDTH;
+
+                        let video_pixel = &mut self.video[pixel_y * VIDEO_WIDTH + pixel_x];
+
+                        if pixel
+                        {
+                            collision |= *video_pixel;
+                            *video_pixel ^= true;
+                            self.redraw = true;
+                        }
+                    }
+                }
+
+                if collision
+                {
+                    self.registers[0xF] = 1;
+                }
+                else
+                {
+                    self.registers[0xF] = 0;
+                }
+
+                self.vblank_wait = self.quirks.display_wait;
+            },
+
+            Instruction::SkipKey { x } =>
+            {
+                if self.key_pressed(self.registers[x])?
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::SkipNotKey { x } =>
+            {
+                if !self.key_pressed(self.registers[x])?
+                {
+                    self.program_counter += 2;
+                }
+            },
+
+            Instruction::LoadDelay { x } => self.registers[x] = self.delay_timer,
+
+            // Fx0A only completes once the pressed key is released again,
+            // otherwise a single press is seen by every following Fx0A
+            Instruction::WaitKey { x } =>
+            {
+                match self.awaited_key
+                {
+                    Some(key) if !self.keypad[key as usize] =>
+                    {
+                        self.registers[x] = key;
+                        self.awaited_key = None;
+                    },
+
+                    Some(_) => self.program_counter -= 2,
+
+                    None =>
+                    {
+                        self.awaited_key = self.check_keypad();
+                        self.program_counter -= 2;
+                    },
+                }
+            },
+
+            Instruction::SetDelay { x } => self.delay_timer = self.registers[x],
+
+            Instruction::SetSound { x } => self.set_sound_timer(self.registers[x]),
+
+            Instruction::AddIndex { x } => self.index = self.index.wrapping_add(self.registers[x] as u16),
+
+            Instruction::LoadFont { x } => self.index = FONT_MEMORY_START + (5 * self.registers[x] as u16),
+
+            Instruction::LoadBigFont { x } => self.index = BIG_FONT_MEMORY_START + (10 * self.registers[x] as u16),
+
+            Instruction::StoreBcd { x } =>
+            {
+                self.memory_range(self.index as usize, 3)?;
+                let mut value = self.registers[x];
+
+                self.mem_write(self.index as usize + 2, value % 10);
+                value /= 10;
+
+                self.mem_write(self.index as usize + 1, value % 10);
+                value /= 10;
+
+                self.mem_write(self.index as usize, value % 10);
+            },
+
+            Instruction::StoreRegisters { x } =>
+            {
+                self.memory_range(self.index as usize, x + 1)?;
+                for i in 0 ..= x
+                {
+                    self.mem_write(self.index as usize + i, self.registers[i]);
+                }
+                self.index = self.index.wrapping_add(self.quirks.index_increment_on_store.amount(x));
+            },
+
+            Instruction::LoadRegisters { x } =>
+            {
+                let start = self.index as usize;
+                self.registers[0 ..= x].copy_from_slice(self.memory.get(start ..= start + x).ok_or(MachineError::MemoryOutOfBounds(self.index))?);
+                self.index = self.index.wrapping_add(self.quirks.index_increment_on_store.amount(x));
+            },
+
+            // SCHIP only has 8 RPL flags, higher registers are ignored
+            Instruction::StoreFlags { x } =>
+            {
+                let n = x.min(NUM_RPL_FLAGS - 1);
+                self.rpl_flags[0 ..= n].copy_from_slice(&self.registers[0 ..= n]);
+            },
+
+            Instruction::LoadFlags { x } =>
+            {
+                let n = x.min(NUM_RPL_FLAGS - 1);
+                self.registers[0 ..= n].copy_from_slice(&self.rpl_flags[0 ..= n]);
+            },
+
+            Instruction::Unknown(opcode) => return Err(MachineError::UnknownOpcode(opcode)),
+        }
+
+        Ok(())
+    }
+}
+
+// The trace, sound callback and watches are closures that can't be copied, so a
+// clone starts without them and they are left out of comparisons
+impl Clone for Chip8
+{
+    fn clone(&self) -> Self
+    {
+        Chip8
+        {
+            registers: self.registers,
+            memory: self.memory,
+
+            program_counter: self.program_counter,
+            index: self.index,
+
+            stack: self.stack,
+            stack_pointer: self.stack_pointer,
+
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+
+            rpl_flags: self.rpl_flags,
+
+            keypad: self.keypad,
+            awaited_key: self.awaited_key,
+            video: self.video,
+            redraw: self.redraw,
+
+            halted: self.halted,
+            halt_on_self_jump: self.halt_on_self_jump,
+            vblank_wait: self.vblank_wait,
+
+            rom_region: self.rom_region,
+            strict: self.strict,
+
+            rng: self.rng.clone(),
+            quirks: self.quirks,
+
+            trace: None,
+            sound_callback: None,
+            watches: Vec::new(),
+            breakpoints: self.breakpoints.clone(),
+            opcode_breakpoints: self.opcode_breakpoints.clone(),
+            resume_breakpoint: self.resume_breakpoint,
+            conditions: self.conditions.clone(),
+
+            cheats: self.cheats.clone(),
+            frozen_registers: self.frozen_registers.clone(),
+
+            instruction_count: self.instruction_count,
+            opcode_histogram: self.opcode_histogram,
+            coverage: self.coverage.clone(),
+            step_history: self.step_history.clone(),
+        }
+    }
+}
+
+impl PartialEq for Chip8
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.registers == other.registers
+            && self.memory == other.memory
+            && self.program_counter == other.program_counter
+            && self.index == other.index
+            && self.stack == other.stack
+            && self.stack_pointer == other.stack_pointer
+            && self.delay_timer == other.delay_timer
+            && self.sound_timer == other.sound_timer
+            && self.rpl_flags == other.rpl_flags
+            && self.keypad == other.keypad
+            && self.awaited_key == other.awaited_key
+            && self.video == other.video
+            && self.redraw == other.redraw
+            && self.halted == other.halted
+            && self.halt_on_self_jump == other.halt_on_self_jump
+            && self.vblank_wait == other.vblank_wait
+            && self.rom_region == other.rom_region
+            && self.strict == other.strict
+            && self.rng == other.rng
+            && self.quirks == other.quirks
+            && self.breakpoints == other.breakpoints
+            && self.opcode_breakpoints == other.opcode_breakpoints
+            && self.resume_breakpoint == other.resume_breakpoint
+            && self.conditions == other.conditions
+            && self.cheats == other.cheats
+            && self.frozen_registers == other.frozen_registers
+            && self.instruction_count == other.instruction_count
+            && self.opcode_histogram == other.opcode_histogram
+            && self.coverage == other.coverage
+            && self.step_history == other.step_history
+    }
+}
+
+// Memory and video are summarised, the whole arrays would bury everything else
+impl fmt::Debug for Chip8
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let pixels_on = self.video.iter().filter(|&&pixel| pixel).count();
+
+        f.debug_struct("Chip8")
+         .field("pc", &format_args!("{:#05x}", self.program_counter))
+         .field("i", &format_args!("{:#05x}", self.index))
+         .field("sp", &self.stack_pointer)
+         .field("registers", &format_args!("{:02X?}", self.registers))
+         .field("delay_timer", &self.delay_timer)
+         .field("sound_timer", &self.sound_timer)
+         .field("memory", &format_args!("{} bytes", self.memory.len()))
+         .field("video", &format_args!("{} pixels on", pixels_on))
+         .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Chip8
+{
+    fn default() -> Self
+    {
+        Chip8::new()
+    }
+}
+
+// Every address whose byte differs as (address, old, new), eg - diffing
+// snapshots from either side of a frame shows a game's working variables
+pub fn diff_memory(old: &[u8; MEMORY_SIZE], new: &[u8; MEMORY_SIZE]) -> Vec<(u16, u8, u8)>
+{
+    old.iter()
+       .zip(new.iter())
+       .enumerate()
+       .filter(|&(_, (a, b))| a != b)
+       .map(|(address, (&a, &b))| (address as u16, a, b))
+       .collect()
+}
+
+// Gzipped ROMs are recognised by their magic bytes, anything else
+// is passed through untouched
+#[cfg(feature = "std")]
+fn decompress(rom: Vec<u8>) -> io::Result<Vec<u8>>
+{
+    if !rom.starts_with(&GZIP_MAGIC)
+    {
+        return Ok(rom);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(&rom[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::quirks::IndexIncrement;
+
+    fn machine_with_program(program: &[u8]) -> Chip8
+    {
+        let mut c = Chip8::new_seeded(0);
+        c.load_bytes(program).unwrap();
+        c
+    }
+
+    #[test]
+    fn tick_leaves_the_timers_alone()
+    {
+        // LD V0, 30; LD DT, V0; ADD V1, 1; JP 0x204
+        let mut c = machine_with_program(&[0x60, 0x1E, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+
+        c.tick(100).unwrap();
+        assert_eq!(c.delay_timer(), 30);
+        assert_eq!(c.register(1), 49);
+        assert_eq!(c.instruction_count(), 100);
+    }
+
+    #[test]
+    fn registers_past_vf_read_as_zero()
+    {
+        // LD VF, 0xFF
+        let mut c = machine_with_program(&[0x6F, 0xFF]);
+        c.step().unwrap();
+
+        assert_eq!(c.register(0xF), 0xFF);
+        assert_eq!(c.register(16), 0);
+        assert_eq!(c.register(usize::MAX), 0);
+    }
+
+    #[test]
+    fn strict_mode_stops_at_the_end_of_the_rom()
+    {
+        // LD V0, 1; ADD V0, 1
+        let mut c = machine_with_program(&[0x60, 0x01, 0x70, 0x01]);
+        c.set_strict(true);
+
+        c.tick(2).unwrap();
+        assert_eq!(c.step(), Err(MachineError::PcOutOfRange(0x204)));
+        assert_eq!(c.program_counter(), 0x204);
+
+        // JP 0x050, into the font
+        let mut c = machine_with_program(&[0x10, 0x50]);
+        c.set_strict(true);
+        c.tick(1).unwrap();
+        assert_eq!(c.step(), Err(MachineError::PcOutOfRange(0x050)));
+    }
+
+    #[test]
+    fn coverage_collects_distinct_opcodes()
+    {
+        // LD V0, 1; ADD V0, 1; JP 0x202
+        let mut c = machine_with_program(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+
+        c.tick(3).unwrap();
+        assert!(c.coverage().is_empty());
+
+        c.set_coverage(true);
+        c.tick(10).unwrap();
+        assert_eq!(c.coverage(), vec![0x1202, 0x7001]);
+    }
+
+    #[test]
+    fn stepping_a_clone_leaves_the_original_alone()
+    {
+        // LD V0, 1; ADD V0, 1
+        let mut c = machine_with_program(&[0x60, 0x01, 0x70, 0x01]);
+        c.step().unwrap();
+
+        let mut copy = c.clone();
+        assert!(copy == c);
+
+        copy.step().unwrap();
+        assert!(copy != c);
+        assert_eq!(c.register(0x0), 1);
+        assert_eq!(c.program_counter(), 0x202);
+        assert_eq!(copy.register(0x0), 2);
+    }
+
+    #[test]
+    fn debug_summarises_memory_and_video()
+    {
+        // LD VA, 0x1F; LD I, 0x050; DRW V0, V0, 5
+        let mut c = machine_with_program(&[0x6A, 0x1F, 0xA0, 0x50, 0xD0, 0x05]);
+        c.tick(3).unwrap();
+
+        let text = format!("{:?}", c);
+        assert!(text.contains("pc: 0x206"), "{}", text);
+        assert!(text.contains("i: 0x050"), "{}", text);
+        assert!(text.contains("registers: [00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 1F,"), "{}", text);
+        assert!(text.contains("memory: 4096 bytes"), "{}", text);
+        assert!(text.contains("video: 14 pixels on"), "{}", text);
+    }
+
+    #[test]
+    fn sound_callback_fires_on_start_and_stop()
+    {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        // LD V0, 2; LD ST, V0; LD ST, V0
+        let mut c = machine_with_program(&[0x60, 0x02, 0xF0, 0x18, 0xF0, 0x18]);
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let log = changes.clone();
+        c.set_sound_callback(Box::new(move |active| log.borrow_mut().push(active)));
+
+        c.tick(3).unwrap();
+        assert!(c.is_sound_active());
+
+        c.decrement_timers();
+        c.decrement_timers();
+        c.decrement_timers();
+        assert!(!c.is_sound_active());
+        assert_eq!(*changes.borrow(), [true, false]);
+    }
+
+    #[test]
+    fn step_back_undoes_instructions()
+    {
+        // LD V0, 1; ADD V0, 1; LD I, 0x300; LD [I], V0
+        let mut c = machine_with_program(&[0x60, 0x01, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55]);
+        c.step().unwrap();
+        assert!(!c.step_back());
+
+        c.set_step_history(true);
+        c.tick(3).unwrap();
+        assert_eq!(c.peek(0x300), 2);
+
+        assert!(c.step_back());
+        assert_eq!(c.peek(0x300), 0);
+        assert_eq!(c.program_counter(), 0x206);
+
+        assert!(c.step_back());
+        assert!(c.step_back());
+        assert_eq!(c.register(0x0), 1);
+        assert_eq!(c.program_counter(), 0x202);
+        assert!(!c.step_back());
+    }
+
+    #[test]
+    fn step_history_is_bounded()
+    {
+        // ADD V0, 1; JP 0x200
+        let mut c = machine_with_program(&[0x70, 0x01, 0x12, 0x00]);
+        c.set_step_history(true);
+        c.tick(200).unwrap();
+
+        let mut undone = 0;
+        while c.step_back()
+        {
+            undone += 1;
+        }
+        assert_eq!(undone, STEP_HISTORY);
+        assert_eq!(c.register(0x0), 68);
+    }
+
+    #[test]
+    fn exports_a_scaled_ppm()
+    {
+        // LD I, 0x050; DRW V0, V0, 5
+        let mut c = machine_with_program(&[0xA0, 0x50, 0xD0, 0x05]);
+        c.tick(2).unwrap();
+
+        let ppm = c.export_ppm(2);
+        let header = b"P6\n128 64\n255\n";
+
+        assert_eq!(&ppm[.. header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 3 * 128 * 64);
+
+        // Both rows of the scaled up top left pixel are lit
+        let pixels = &ppm[header.len() ..];
+        assert_eq!(&pixels[0 .. 6], &[0xFF; 6]);
+        assert_eq!(&pixels[3 * 128 .. 3 * 128 + 6], &[0xFF; 6]);
+        assert_eq!(&pixels[3 * 8 .. 3 * 9], &[0x00; 3]);
+    }
+
+    #[test]
+    fn opcode_breakpoints_stop_before_matching_instructions()
+    {
+        // LD I, 0x050; CALL 0x208; DRW V0, V0, 1; JP 0x206; RET
+        let mut c = machine_with_program(&[0xA0, 0x50, 0x22, 0x08, 0xD0, 0x01, 0x12, 0x06, 0x00, 0xEE]);
+        c.break_on_opcode_mask(0xF000, 0xD000);
+        c.break_on_opcode_mask(0xF000, 0x2000);
+
+        c.step().unwrap();
+        assert_eq!(c.step(), Err(MachineError::Breakpoint(0x202)));
+        assert_eq!(c.stack_depth(), 0);
+
+        c.tick(2).unwrap();
+        assert_eq!(c.step(), Err(MachineError::Breakpoint(0x204)));
+        assert!(c.video_buffer().iter().all(|&pixel| !pixel));
+
+        c.clear_opcode_breakpoints();
+        c.tick(1).unwrap();
+        assert!(c.pixel(0, 0));
+    }
+
+    #[test]
+    fn frozen_registers_keep_their_value()
+    {
+        // ADD V3, 1; LD V5, 9; ADD VF, V5; JP 0x200
+        let mut c = machine_with_program(&[0x73, 0x01, 0x65, 0x09, 0x8F, 0x54, 0x12, 0x00]);
+        c.freeze_register(0x3, 3);
+        c.freeze_register(0xF, 0);
+        assert_eq!(c.register(0x3), 3);
+
+        c.tick(8).unwrap();
+        assert_eq!(c.register(0x3), 3);
+        assert_eq!(c.register(0xF), 0);
+        assert_eq!(c.register(0x5), 9);
+
+        c.unfreeze_register(0x3);
+        c.tick(1).unwrap();
+        assert_eq!(c.register(0x3), 4);
+    }
+
+    #[test]
+    fn reaching_past_memory_is_an_error()
+    {
+        // LD I, 0xFFE; then one of the instructions below
+        for &(opcode, error) in &[(0xF355, MachineError::MemoryOutOfBounds(0xFFE)),
+                                  (0xF365, MachineError::MemoryOutOfBounds(0xFFE)),
+                                  (0xF033, MachineError::MemoryOutOfBounds(0xFFE)),
+                                  (0xD015, MachineError::MemoryOutOfBounds(0xFFE))]
+        {
+            let [high, low] = (opcode as u16).to_be_bytes();
+            let mut c = machine_with_program(&[0xAF, 0xFE, high, low]);
+            c.step().unwrap();
+
+            assert_eq!(c.step(), Err(error));
+            assert_eq!(c.program_counter(), 0x202);
+            assert_eq!(c.memory()[0xFFE ..], [0, 0]);
+        }
+
+        // LD V0, 0x20; SKP V0
+        let mut c = machine_with_program(&[0x60, 0x20, 0xE0, 0x9E]);
+        c.step().unwrap();
+        assert_eq!(c.step(), Err(MachineError::InvalidKey(0x20)));
+
+        // JP 0xFFF
+        let mut c = machine_with_program(&[0x1F, 0xFF]);
+        c.step().unwrap();
+        assert_eq!(c.step(), Err(MachineError::MemoryOutOfBounds(0xFFF)));
+    }
+
+    #[test]
+    fn call_past_stack_depth_overflows()
+    {
+        // CALL 0x200
+        let mut c = machine_with_program(&[0x22, 0x00]);
+
+        for _i in 0..16
+        {
+            assert_eq!(c.step(), Ok(0x2200));
+        }
+
+        assert_eq!(c.step(), Err(MachineError::StackOverflow));
+        assert_eq!(c.program_counter(), ROM_MEMORY_START);
+    }
+
+    #[test]
+    fn return_on_empty_stack_underflows()
+    {
+        // RET
+        let mut c = machine_with_program(&[0x00, 0xEE]);
+
+        assert_eq!(c.step(), Err(MachineError::StackUnderflow));
+        assert_eq!(c.stack_pointer(), 0);
+    }
+
+    #[test]
+    fn watches_fire_only_for_the_watched_address()
+    {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        // LD V0, 123; LD I, 0x300; LD B, V0; LD [I], V0
+        let mut c = machine_with_program(&[0x60, 0x7B, 0xA3, 0x00, 0xF0, 0x33, 0xF0, 0x55]);
+
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let log = writes.clone();
+        c.watch_memory(0x301, Box::new(move |address, old, new| log.borrow_mut().push((address, old, new))));
+
+        for _i in 0..4
+        {
+            c.step().unwrap();
+        }
+
+        assert_eq!(*writes.borrow(), [(0x301, 0, 2)]);
+        assert_eq!(c.peek(0x300), 123);
+    }
+
+    #[test]
+    fn breakpoints_stop_before_the_instruction_once()
+    {
+        // ADD V0, 1; JP 0x200
+        let mut c = machine_with_program(&[0x70, 0x01, 0x12, 0x00]);
+        c.add_breakpoint(0x200);
+
+        assert_eq!(c.step(), Err(MachineError::Breakpoint(0x200)));
+        assert_eq!(c.register(0), 0);
+
+        assert_eq!(c.step(), Ok(0x7001));
+        assert_eq!(c.step(), Ok(0x1200));
+        assert_eq!(c.step(), Err(MachineError::Breakpoint(0x200)));
+        assert_eq!(c.register(0), 1);
+
+        c.remove_breakpoint(0x200);
+        assert_eq!(c.step(), Ok(0x7001));
+    }
+
+    #[test]
+    fn conditions_stop_after_the_register_changes()
+    {
+        // ADD V3, 0x7F; LD V1, 2; JP 0x200
+        let mut c = machine_with_program(&[0x73, 0x7F, 0x61, 0x02, 0x12, 0x00]);
+        c.add_watch_condition(0x3, 0xFE);
+        c.add_watch_condition(0x1, 0x02);
+
+        assert_eq!(c.step(), Ok(0x737F));
+        assert_eq!(c.step(), Err(MachineError::ConditionMet(0x1, 0x02)));
+        assert_eq!(c.program_counter(), 0x204);
+
+        assert_eq!(c.step(), Ok(0x1200));
+        assert_eq!(c.step(), Err(MachineError::ConditionMet(0x3, 0xFE)));
+        assert_eq!(c.step(), Ok(0x6102));
+    }
+
+    // Sets V1 and V2, or VF and V2 when x is F, then steps 8xyN
+    fn arithmetic(x: u8, n: u8, vx: u8, vy: u8) -> Chip8
+    {
+        let mut c = machine_with_program(&[0x60 | x, vx, 0x62, vy, 0x80 | x, 0x20 | n]);
+
+        for _i in 0..3
+        {
+            c.step().unwrap();
+        }
+
+        c
+    }
+
+    #[test]
+    fn add_sets_carry()
+    {
+        let c = arithmetic(0x1, 0x4, 0x10, 0x20);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x30, 0));
+
+        let c = arithmetic(0x1, 0x4, 0xF0, 0x20);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x10, 1));
+    }
+
+    #[test]
+    fn sub_sets_not_borrow()
+    {
+        let c = arithmetic(0x1, 0x5, 0x30, 0x20);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x10, 1));
+
+        let c = arithmetic(0x1, 0x5, 0x20, 0x30);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0xF0, 0));
+
+        let c = arithmetic(0x1, 0x5, 0x20, 0x20);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x00, 1));
+    }
+
+    #[test]
+    fn subn_sets_not_borrow()
+    {
+        let c = arithmetic(0x1, 0x7, 0x20, 0x30);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x10, 1));
+
+        let c = arithmetic(0x1, 0x7, 0x30, 0x20);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0xF0, 0));
+    }
+
+    #[test]
+    fn shifts_set_the_shifted_out_bit()
+    {
+        let c = arithmetic(0x1, 0x6, 0x05, 0x00);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x02, 1));
+
+        let c = arithmetic(0x1, 0x6, 0x04, 0x00);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x02, 0));
+
+        let c = arithmetic(0x1, 0xE, 0x81, 0x00);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x02, 1));
+
+        let c = arithmetic(0x1, 0xE, 0x41, 0x00);
+        assert_eq!((c.register(0x1), c.register(0xF)), (0x82, 0));
+    }
+
+    #[test]
+    fn flag_overwrites_the_result_when_x_is_vf()
+    {
+        assert_eq!(arithmetic(0xF, 0x4, 0xF0, 0x20).register(0xF), 1);
+        assert_eq!(arithmetic(0xF, 0x4, 0x10, 0x20).register(0xF), 0);
+        assert_eq!(arithmetic(0xF, 0x5, 0x30, 0x20).register(0xF), 1);
+        assert_eq!(arithmetic(0xF, 0x5, 0x20, 0x30).register(0xF), 0);
+        assert_eq!(arithmetic(0xF, 0x6, 0x05, 0x00).register(0xF), 1);
+        assert_eq!(arithmetic(0xF, 0x7, 0x20, 0x30).register(0xF), 1);
+        assert_eq!(arithmetic(0xF, 0xE, 0x41, 0x00).register(0xF), 0);
+    }
+
+    #[test]
+    fn unknown_opcodes_are_errors()
+    {
+        let mut c = machine_with_program(&[0xE0, 0xFF]);
+
+        assert_eq!(c.step(), Err(MachineError::UnknownOpcode(0xE0FF)));
+        assert_eq!(c.program_counter(), ROM_MEMORY_START);
+    }
+
+    #[test]
+    fn exit_halts_the_machine()
+    {
+        // LD V0, 1; EXIT; LD V0, 2
+        let mut c = machine_with_program(&[0x60, 0x01, 0x00, 0xFD, 0x60, 0x02]);
+
+        c.step().unwrap();
+        assert_eq!(c.step(), Ok(0x00FD));
+        assert!(c.is_halted());
+
+        assert_eq!(c.step(), Err(MachineError::Halted));
+        assert_eq!(c.register(0), 1);
+    }
+
+    #[test]
+    fn self_jumps_only_halt_when_enabled()
+    {
+        // JP 0x200
+        let mut c = machine_with_program(&[0x12, 0x00]);
+
+        c.step().unwrap();
+        assert!(!c.is_halted());
+
+        c.set_halt_on_self_jump(true);
+        c.step().unwrap();
+        assert!(c.is_halted());
+    }
+
+    #[test]
+    fn find_byte_narrows_down_changing_values()
+    {
+        let mut c = machine_with_program(&[0xAB, 0x00, 0xAB]);
+        c.poke(0x300, 0xAB);
+
+        assert_eq!(c.find_byte_in(0xAB, ROM_MEMORY_START, 0x1000), [0x200, 0x202, 0x300]);
+        assert_eq!(c.find_byte_in(0xAB, 0x201, 0x300), [0x202]);
+        assert_eq!(c.find_byte_in(0xAB, 0x300, 0x200), []);
+
+        c.poke(0x202, 0xAC);
+        let previous = c.find_byte(0xAB);
+        let now = c.find_byte(0xAC);
+        assert!(previous.contains(&0x300) && !previous.contains(&0x202));
+        assert_eq!(now, [0x202]);
+    }
+
+    #[test]
+    fn roms_can_load_at_other_addresses()
+    {
+        let mut c = Chip8::new_seeded(0);
+        c.load_bytes_at(&[0x60, 0x05], ETI_660_MEMORY_START).unwrap();
+
+        assert_eq!(c.program_counter(), 0x600);
+        assert_eq!(c.step(), Ok(0x6005));
+
+        assert!(matches!(c.load_bytes_at(&[0; 4], 0x1000), Err(LoadError::InvalidAddress(0x1000))));
+        assert!(matches!(c.load_bytes_at(&[0; 4], 0xFFE), Err(LoadError::RomTooLarge(4))));
+    }
+
+    #[test]
+    fn custom_fonts_stop_at_the_big_font()
+    {
+        let mut c = Chip8::new_seeded(0);
+        let big_font_start = BIG_FONT_MEMORY_START as usize;
+
+        c.load_font(&[0xFF; 80]).unwrap();
+        assert_eq!(c.memory[FONT_MEMORY_START as usize], 0xFF);
+
+        assert!(matches!(c.load_font(&[0xAA; 81]), Err(LoadError::FontTooLarge(81))));
+        assert!(matches!(c.load_font(&[0xAA; 0x200]), Err(LoadError::FontTooLarge(0x200))));
+        assert_eq!(c.memory[big_font_start .. big_font_start + BIG_FONTS.len()], BIG_FONTS[..]);
+        assert_eq!(c.memory[FONT_MEMORY_START as usize], 0xFF);
+    }
+
+    #[test]
+    fn reset_clears_the_program_but_not_the_fonts()
+    {
+        // LD V3, 0x20; CALL 0x206; EXIT; CLS
+        let mut c = machine_with_program(&[0x63, 0x20, 0x22, 0x06, 0x00, 0xFD, 0x00, 0xE0]);
+        let fonts = c.memory[.. ROM_MEMORY_START as usize].to_vec();
+        c.step().unwrap();
+        c.step().unwrap();
+
+        c.reset();
+
+        assert_eq!(c.register(0x3), 0);
+        assert_eq!(c.program_counter(), ROM_MEMORY_START);
+        assert_eq!(c.peek(0x200), 0);
+        assert_eq!(&c.memory[.. ROM_MEMORY_START as usize], &fonts[..]);
+        assert_eq!(c.instruction_count(), 0);
+
+        c.load_bytes(&[0x00, 0xEE]).unwrap();
+        assert_eq!(c.step(), Err(MachineError::StackUnderflow));
+    }
+
+    #[test]
+    fn intel_hex_is_written_at_its_addresses()
+    {
+        let mut c = Chip8::new_seeded(0);
+        c.load_hex(":04020000600561FF35\n:0203000000E01B\n:00000001FF\n").unwrap();
+
+        assert_eq!(c.peek(0x203), 0xFF);
+        assert_eq!(c.peek(0x301), 0xE0);
+        assert_eq!(c.step(), Ok(0x6005));
+
+        assert!(matches!(c.load_hex(":02FFFF00000000\n:00000001FF\n"), Err(LoadError::InvalidAddress(0xFFFF))));
+    }
+
+    #[test]
+    fn gzipped_roms_are_decompressed()
+    {
+        let rom = vec![0x60, 0x05, 0x61, 0xFF];
+        let gzipped = vec![0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03,
+                           0x01, 0x04, 0x00, 0xFB, 0xFF, 0x60, 0x05, 0x61, 0xFF,
+                           0x9F, 0x5F, 0x4D, 0x4D, 0x04, 0x00, 0x00, 0x00];
+
+        assert_eq!(decompress(gzipped).unwrap(), rom);
+        assert_eq!(decompress(rom.clone()).unwrap(), rom);
+        assert!(decompress(vec![0x1F, 0x8B, 0x08]).is_err());
+    }
+
+    #[test]
+    fn diffs_memory_across_a_frame()
+    {
+        // LD V0, 7; LD I, 0x300; LD [I], V0
+        let mut c = machine_with_program(&[0x60, 0x07, 0xA3, 0x00, 0xF0, 0x55]);
+        let before = c.snapshot_memory();
+
+        c.run_for(3).unwrap();
+
+        assert_eq!(diff_memory(&before, &c.snapshot_memory()), [(0x300, 0x00, 0x07)]);
+        assert_eq!(diff_memory(&before, &before), []);
+    }
+
+    #[test]
+    fn logic_resets_vf_only_with_the_quirk()
+    {
+        for &n in [0x1, 0x2, 0x3].iter()
+        {
+            // LD VF, 1; LD V1, 0x0C; LD V2, 0x0A; 8 1 2 n
+            let program = [0x6F, 0x01, 0x61, 0x0C, 0x62, 0x0A, 0x81, 0x20 | n];
+
+            for &reset_vf_on_logic in [true, false].iter()
+            {
+                let mut c = machine_with_program(&program);
+                c.set_quirks(Quirks { reset_vf_on_logic, ..Quirks::default() });
+
+                for _i in 0..4
+                {
+                    c.step().unwrap();
+                }
+
+                let expected = match n
+                {
+                    0x1 => 0x0E,
+                    0x2 => 0x08,
+                    _ => 0x06,
+                };
+                assert_eq!(c.register(0x1), expected);
+                assert_eq!(c.register(0xF), !reset_vf_on_logic as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn store_and_load_move_the_index_per_quirk()
+    {
+        let table =
+        [
+            (IndexIncrement::ByXPlusOne, 0x304),
+            (IndexIncrement::ByX, 0x303),
+            (IndexIncrement::Unchanged, 0x300),
+        ];
+
+        for &(index_increment_on_store, expected) in table.iter()
+        {
+            // LD I, 0x300; LD [I], V3; LD I, 0x300; LD V3, [I]
+            let mut c = machine_with_program(&[0xA3, 0x00, 0xF3, 0x55, 0xA3, 0x00, 0xF3, 0x65]);
+            c.set_quirks(Quirks { index_increment_on_store, ..Quirks::default() });
+
+            c.tick(2).unwrap();
+            assert_eq!(c.index(), expected);
+
+            c.tick(2).unwrap();
+            assert_eq!(c.index(), expected);
+        }
+    }
+
+    #[test]
+    fn jump_offset_register_depends_on_the_quirk()
+    {
+        for &(jump_uses_vx, expected) in [(false, 0x312), (true, 0x334)].iter()
+        {
+            // LD V0, 0x02; LD V3, 0x24; JP V0, 0x310
+            let mut c = machine_with_program(&[0x60, 0x02, 0x63, 0x24, 0xB3, 0x10]);
+            c.set_quirks(Quirks { jump_uses_vx, ..Quirks::default() });
+
+            c.tick(3).unwrap();
+            assert_eq!(c.program_counter(), expected);
+        }
+    }
+
+    #[test]
+    fn display_wait_holds_the_cpu_until_vblank()
+    {
+        // LD I, 0x050; DRW V0, V0, 1; ADD V1, 1
+        let mut c = machine_with_program(&[0xA0, 0x50, 0xD0, 0x01, 0x71, 0x01]);
+        c.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+
+        c.cycle().unwrap();
+        assert_eq!(c.cycle(), Ok(true));
+        assert!(c.is_waiting_for_vblank());
+
+        assert_eq!(c.cycle(), Ok(false));
+        assert_eq!(c.register(0x1), 0);
+
+        c.vblank();
+        c.cycle().unwrap();
+        assert_eq!(c.register(0x1), 1);
+    }
+
+    #[test]
+    fn dxy0_draws_a_16x16_sprite()
+    {
+        // LD V0, 8; LD V1, 4; LD I, 0x300; DRW V0, V1, 0; DRW V0, V1, 0
+        let mut c = machine_with_program(&[0x60, 0x08, 0x61, 0x04, 0xA3, 0x00, 0xD0, 0x10, 0xD0, 0x10]);
+        c.memory[0x300 .. 0x320].copy_from_slice(&[0xFF; 32]);
+
+        for _i in 0..4
+        {
+            c.step().unwrap();
+        }
+
+        for y in 0..VIDEO_HEIGHT
+        {
+            for x in 0..VIDEO_WIDTH
+            {
+                let inside = (8..24).contains(&x) && (4..20).contains(&y);
+                assert_eq!(c.pixel(x, y), inside, "pixel ({}, {})", x, y);
+            }
+        }
+        assert_eq!(c.register(0xF), 0);
+
+        c.step().unwrap();
+        assert!(c.video_buffer().iter().all(|&pixel| !pixel));
+        assert_eq!(c.register(0xF), 1);
+    }
+
+    // LD V0, 62; LD V1, 30; LD I, 0x300; DRW V0, V1, 4
+    const EDGE_SPRITE: [u8; 8] = [0x60, 62, 0x61, 30, 0xA3, 0x00, 0xD0, 0x14];
+
+    fn draw_at_edge(wrap_sprites: bool) -> Chip8
+    {
+        let mut c = machine_with_program(&EDGE_SPRITE);
+        c.memory[0x300 .. 0x304].copy_from_slice(&[0xFF; 4]);
+        c.set_quirks(Quirks { wrap_sprites, ..Quirks::default() });
+
+        for _i in 0..4
+        {
+            c.step().unwrap();
+        }
+        c
+    }
+
+    #[test]
+    fn sprites_clip_at_the_edges()
+    {
+        let c = draw_at_edge(false);
+
+        assert_eq!(c.video_buffer().iter().filter(|&&pixel| pixel).count(), 4);
+        for &(x, y) in &[(62, 30), (63, 30), (62, 31), (63, 31)]
+        {
+            assert!(c.pixel(x, y));
+        }
+    }
+
+    #[test]
+    fn sprites_wrap_around_the_edges()
+    {
+        let c = draw_at_edge(true);
+
+        assert_eq!(c.video_buffer().iter().filter(|&&pixel| pixel).count(), 32);
+        for &(x, y) in &[(62, 30), (63, 31), (0, 30), (5, 31), (62, 0), (63, 1), (0, 0), (5, 1)]
+        {
+            assert!(c.pixel(x, y));
         }
+        assert!(!c.pixel(6, 0));
+        assert!(!c.pixel(62, 2));
     }
 }
@@ -1,13 +1,403 @@
-mod emulator;
-mod fonts;
-mod machine;
+use std::env;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use emulator::Emulator;
-use machine::Chip8;
+use rusty_chip::config::Config;
+use rusty_chip::flags;
+use rusty_chip::disasm::disassemble;
+#[cfg(feature = "sdl")]
+use rusty_chip::frontend::sdl::Sdl;
+use rusty_chip::frontend::terminal::Terminal;
+use rusty_chip::frontend::Frontend;
+use rusty_chip::replay::Playback;
+use rusty_chip::machine::ROM_MEMORY_START;
+use rusty_chip::{Chip8, Emulator, LoadError, Platform};
+
+const USAGE: &str = "Usage: rusty-chip <rom> [--scale <factor>] [--speed <cycles per frame>] [--clock <hz>] [--backend <ggez|terminal|sdl2>] [--fps <cap>] [--no-vsync] [--exit-on-halt] [--strict] [--load-address <address>] [--platform <vip|chip48|schip|xochip>] [--seed <n>] [--record <file>] [--replay <file>] [--break <address>]... [--config <file>] [--coverage <cycles>] [--flags-dir <dir>]";
+
+const DEFAULT_SCALE: f32 = 10.0;
+const DEFAULT_SPEED: u32 = 8;
+
+enum Backend
+{
+    Ggez,
+    Terminal,
+    #[cfg(feature = "sdl")]
+    Sdl2,
+}
+
+struct Args
+{
+    rom: String,
+    scale: Option<f32>,
+    speed: Option<u32>,
+    clock: Option<u32>,
+    backend: Backend,
+    fps: u32,
+    vsync: bool,
+    exit_on_halt: bool,
+    strict: bool,
+    load_address: u16,
+    platform: Option<Platform>,
+    seed: Option<u64>,
+    record: Option<String>,
+    replay: Option<String>,
+    breakpoints: Vec<u16>,
+    config: Option<String>,
+    coverage: Option<usize>,
+    flags_dir: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String>
+{
+    let mut rom = None;
+    let mut scale = None;
+    let mut speed = None;
+    let mut clock = None;
+    let mut backend = Backend::Ggez;
+    let mut fps = 0;
+    let mut vsync = true;
+    let mut exit_on_halt = false;
+    let mut strict = false;
+    let mut load_address = ROM_MEMORY_START;
+    let mut platform = None;
+    let mut seed = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut breakpoints = Vec::new();
+    let mut config = None;
+    let mut coverage = None;
+    let mut flags_dir = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--scale" =>
+            {
+                let value = args.next().ok_or("Missing Value For --scale")?;
+                scale = Some(value.parse().map_err(|_| format!("Invalid Scale: {}", value))?);
+            },
+
+            "--speed" =>
+            {
+                let value = args.next().ok_or("Missing Value For --speed")?;
+                speed = Some(value.parse().map_err(|_| format!("Invalid Speed: {}", value))?);
+            },
+
+            "--clock" =>
+            {
+                let value = args.next().ok_or("Missing Value For --clock")?;
+                clock = Some(value.parse().map_err(|_| format!("Invalid Clock: {}", value))?);
+            },
+
+            "--backend" =>
+            {
+                backend = match args.next().as_deref()
+                {
+                    Some("ggez") => Backend::Ggez,
+                    Some("terminal") => Backend::Terminal,
+                    #[cfg(feature = "sdl")]
+                    Some("sdl2") => Backend::Sdl2,
+                    #[cfg(not(feature = "sdl"))]
+                    Some("sdl2") => return Err(String::from("The sdl2 Backend Needs The sdl Feature")),
+                    Some(other) => return Err(format!("Unknown Backend: {}", other)),
+                    None => return Err(String::from("Missing Value For --backend")),
+                };
+            },
+
+            "--fps" =>
+            {
+                let value = args.next().ok_or("Missing Value For --fps")?;
+                fps = value.parse().map_err(|_| format!("Invalid FPS: {}", value))?;
+            },
+
+            "--no-vsync" => vsync = false,
+
+            "--exit-on-halt" => exit_on_halt = true,
+
+            "--strict" => strict = true,
+
+            "--load-address" =>
+            {
+                let value = args.next().ok_or("Missing Value For --load-address")?;
+                load_address = parse_address(&value)?;
+            },
+
+            "--platform" =>
+            {
+                let value = args.next().ok_or("Missing Value For --platform")?;
+                platform = Some(Platform::from_name(&value).ok_or_else(|| format!("Unknown Platform: {}", value))?);
+            },
+
+            "--seed" =>
+            {
+                let value = args.next().ok_or("Missing Value For --seed")?;
+                seed = Some(value.parse().map_err(|_| format!("Invalid Seed: {}", value))?);
+            },
+
+            "--record" => record = Some(args.next().ok_or("Missing Value For --record")?),
+
+            "--replay" => replay = Some(args.next().ok_or("Missing Value For --replay")?),
+
+            "--break" =>
+            {
+                let value = args.next().ok_or("Missing Value For --break")?;
+                breakpoints.push(parse_address(&value)?);
+            },
+
+            "--config" => config = Some(args.next().ok_or("Missing Value For --config")?),
+
+            "--coverage" =>
+            {
+                let value = args.next().ok_or("Missing Value For --coverage")?;
+                coverage = Some(value.parse().map_err(|_| format!("Invalid Cycle Count: {}", value))?);
+            },
+
+            "--flags-dir" => flags_dir = Some(args.next().ok_or("Missing Value For --flags-dir")?),
+
+            "-h" | "--help" => return Err(String::new()),
+
+            _ if rom.is_none() => rom = Some(arg),
+
+            _ => return Err(format!("Unexpected Argument: {}", arg)),
+        }
+    }
+
+    let rom = rom.ok_or("No ROM Given")?;
+
+    if !matches!(backend, Backend::Ggez) && (record.is_some() || replay.is_some())
+    {
+        return Err(String::from("Recording And Replays Need The ggez Backend"));
+    }
+
+    Ok(Args { rom, scale, speed, clock, backend, fps, vsync, exit_on_halt, strict, load_address, platform, seed, record, replay, breakpoints, config, coverage, flags_dir })
+}
+
+// Addresses are always hex, with or without a leading 0x
+fn parse_address(value: &str) -> Result<u16, String>
+{
+    let digits = value.trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid Address: {}", value))
+}
 
 fn main()
 {
-    let e = &mut Emulator::new(Chip8::new(), 10.0);
-    e.load("space.ch8");
+    let args = match parse_args()
+    {
+        Ok(args) => args,
+        Err(message) =>
+        {
+            if !message.is_empty()
+            {
+                eprintln!("{}", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let config = load_config(&args);
+
+    if let Some(cycles) = args.coverage
+    {
+        print_coverage(&args, &config, cycles);
+        return;
+    }
+
+    match args.backend
+    {
+        Backend::Ggez => run_ggez(&args, &config),
+        Backend::Terminal => run_frontend(&args, &config, || Ok(Terminal::new()?)),
+        #[cfg(feature = "sdl")]
+        Backend::Sdl2 =>
+        {
+            let scale = args.scale.or(config.scale).unwrap_or(DEFAULT_SCALE) as u32;
+            run_frontend(&args, &config, || Ok(Sdl::new(scale)?))
+        },
+    }
+}
+
+// A broken config is only a warning, the defaults are used instead.
+// Options given on the command line win over the config
+fn load_config(args: &Args) -> Config
+{
+    let path = match &args.config
+    {
+        Some(path) => PathBuf::from(path),
+        None => match Config::default_path()
+        {
+            Some(path) if path.exists() => path,
+            _ => return Config::default(),
+        },
+    };
+
+    Config::load(&path).unwrap_or_else(|error|
+    {
+        eprintln!("Warning: {}", error);
+        Config::default()
+    })
+}
+
+fn run_ggez(args: &Args, config: &Config)
+{
+    let scale = args.scale.or(config.scale).unwrap_or(DEFAULT_SCALE);
+    let e = &mut Emulator::new(new_machine(args, config), scale);
+    e.set_cycles_per_frame(args.speed.or(config.cycles_per_frame).unwrap_or(DEFAULT_SPEED));
+    e.set_target_fps(args.fps);
+    e.set_vsync(args.vsync);
+    e.set_exit_on_halt(args.exit_on_halt);
+    e.set_flags_dir(flags_dir(args, config));
+    e.set_auto_quirks(args.platform.is_none() && !config.sets_quirks());
+    if let Some(hz) = args.clock
+    {
+        e.set_clock_hz(hz);
+    }
+    if let Some(controls) = config.controls
+    {
+        e.set_controls(controls);
+    }
+    let mut palette = e.palette();
+    palette[0] = config.background.unwrap_or(palette[0]);
+    palette[1] = config.foreground.unwrap_or(palette[1]);
+    e.set_palette(palette);
+
+    // The window still opens so that the error isn't missed when
+    // started from a file manager instead of a terminal
+    if let Err(error) = e.load_at(&args.rom, args.load_address)
+    {
+        let message = match &error
+        {
+            LoadError::Io(io_error) if io_error.kind() == io::ErrorKind::NotFound => format!("ROM Not Found: {}", args.rom),
+            _ => error.to_string(),
+        };
+
+        eprintln!("{}", message);
+        e.show_message(message);
+    }
+
+    if let Some(path) = &args.replay
+    {
+        match Playback::load(path)
+        {
+            Ok(playback) => e.set_playback(playback),
+            Err(error) =>
+            {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+    if args.record.is_some()
+    {
+        e.start_recording();
+    }
+
     e.create_display();
+
+    if let Some(path) = &args.record
+    {
+        if let Err(error) = e.save_recording(path)
+        {
+            eprintln!("Error Saving Recording: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+// The terminal and SDL2 backends both just hand the machine a Frontend,
+// which is only created once the ROM has loaded
+fn run_frontend<F: Frontend, N: FnOnce() -> Result<F, Box<dyn Error>>>(args: &Args, config: &Config, new_frontend: N)
+{
+    let mut machine = new_machine(args, config);
+    if let Err(error) = machine.load_at(&args.rom, args.load_address)
+    {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    let flags_path = flags_dir(args, config).and_then(|dir| flags::path_for(&dir, Path::new(&args.rom)));
+    if let Some(path) = &flags_path
+    {
+        machine.set_rpl_flags(flags::load(path));
+    }
+
+    let speed = args.speed.or(config.cycles_per_frame).unwrap_or(DEFAULT_SPEED);
+    let cycles_per_frame = args.clock.map_or(speed, |hz| hz / 60);
+
+    let result = new_frontend().and_then(|mut frontend| Ok(machine.run(&mut frontend, cycles_per_frame)?));
+
+    if let Some(path) = &flags_path
+    {
+        if let Err(error) = flags::save(path, machine.rpl_flags())
+        {
+            eprintln!("Error Saving Flags {}: {}", path.display(), error);
+        }
+    }
+
+    if let Err(error) = result
+    {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+// The command line wins over the config, which wins over the default
+fn flags_dir(args: &Args, config: &Config) -> Option<PathBuf>
+{
+    args.flags_dir.as_ref().map(PathBuf::from)
+        .or_else(|| config.flags_dir.clone())
+        .or_else(flags::default_dir)
+}
+
+// Runs without any frontend and lists every distinct opcode the ROM
+// executed, a ROM that stops early still gets what ran before it stopped
+fn print_coverage(args: &Args, config: &Config, cycles: usize)
+{
+    let mut machine = new_machine(args, config);
+    if let Err(error) = machine.load_at(&args.rom, args.load_address)
+    {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    machine.set_coverage(true);
+    if let Err(error) = machine.run_for(cycles)
+    {
+        eprintln!("Stopped Early: {}", error);
+    }
+
+    for opcode in machine.coverage()
+    {
+        println!("{:04X}  {}", opcode, disassemble(opcode));
+    }
+}
+
+// Replays are only bit exact when the RNG starts from the same seed
+fn new_machine(args: &Args, config: &Config) -> Chip8
+{
+    let mut machine = match args.seed
+    {
+        Some(seed) => Chip8::new_seeded(seed),
+        None => Chip8::new(),
+    };
+
+    // Known ROMs pick their own platform unless one was asked for
+    let platform = match args.platform
+    {
+        None if !config.sets_quirks() => Path::new(&args.rom).file_stem().and_then(|stem| Platform::detect(&stem.to_string_lossy())),
+        platform => platform,
+    };
+    machine.set_quirks(config.quirks(platform));
+    machine.set_strict(args.strict);
+
+    for &address in &args.breakpoints
+    {
+        machine.add_breakpoint(address);
+    }
+
+    machine
 }
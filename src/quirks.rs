@@ -0,0 +1,205 @@
+// Behaviours that differ between CHIP-8 interpreters, ROMs tend
+// to only run correctly on the one they were written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks
+{
+    // Sprites wrap around the screen edges instead of being clipped,
+    // the starting coordinate always wraps either way
+    pub wrap_sprites: bool,
+
+    // 8xy1, 8xy2 and 8xy3 clear VF like on the COSMAC VIP
+    pub reset_vf_on_logic: bool,
+
+    // Dxyn waits for the next frame before the CPU carries on, which
+    // limits a ROM to one draw per frame like the VIP's vertical blank
+    pub display_wait: bool,
+
+    // How far Fx55 and Fx65 move I once the registers are copied
+    pub index_increment_on_store: IndexIncrement,
+
+    // Bnnn jumps to nnn + Vx, x being the top nibble of nnn, like
+    // CHIP-48 and SCHIP, instead of nnn + V0 like the VIP
+    pub jump_uses_vx: bool,
+}
+
+// The VIP leaves I just past the last register, CHIP-48 got this
+// off by one and SCHIP stopped touching I at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement
+{
+    ByXPlusOne,
+    ByX,
+    Unchanged,
+}
+
+impl IndexIncrement
+{
+    pub fn from_name(name: &str) -> Option<IndexIncrement>
+    {
+        let increment = match name.to_lowercase().as_str()
+        {
+            "x_plus_one" => IndexIncrement::ByXPlusOne,
+            "x" => IndexIncrement::ByX,
+            "unchanged" => IndexIncrement::Unchanged,
+            _ => return None,
+        };
+
+        Some(increment)
+    }
+
+    pub(crate) fn amount(self, x: usize) -> u16
+    {
+        match self
+        {
+            IndexIncrement::ByXPlusOne => x as u16 + 1,
+            IndexIncrement::ByX => x as u16,
+            IndexIncrement::Unchanged => 0,
+        }
+    }
+}
+
+impl Default for Quirks
+{
+    fn default() -> Self
+    {
+        Quirks
+        {
+            wrap_sprites: false,
+            reset_vf_on_logic: true,
+            display_wait: false,
+            index_increment_on_store: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+        }
+    }
+}
+
+// Well known ROMs and the platform they need, names are compared
+// lowercase with everything but letters and digits dropped
+const KNOWN_ROMS: [(&str, Platform); 13] =
+[
+    ("pong", Platform::CosmacVip),
+    ("brix", Platform::CosmacVip),
+    ("blitz", Platform::CosmacVip),
+    ("tetris", Platform::CosmacVip),
+    ("tank", Platform::CosmacVip),
+    ("ufo", Platform::CosmacVip),
+    ("spaceinvaders", Platform::Chip48),
+    ("blinky", Platform::Chip48),
+    ("spacefight2091", Platform::SuperChip),
+    ("ant", Platform::SuperChip),
+    ("blackrainbow", Platform::XoChip),
+    ("superneatboy", Platform::XoChip),
+    ("chickenscratch", Platform::XoChip),
+];
+
+// The interpreters ROMs are usually written for, each one just
+// picks the quirks that interpreter had
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform
+{
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform
+{
+    pub fn quirks(self) -> Quirks
+    {
+        match self
+        {
+            Platform::CosmacVip => Quirks
+            {
+                wrap_sprites: false,
+                reset_vf_on_logic: true,
+                display_wait: true,
+                index_increment_on_store: IndexIncrement::ByXPlusOne,
+                jump_uses_vx: false,
+            },
+
+            Platform::Chip48 => Quirks
+            {
+                wrap_sprites: false,
+                reset_vf_on_logic: false,
+                display_wait: false,
+                index_increment_on_store: IndexIncrement::ByX,
+                jump_uses_vx: true,
+            },
+
+            Platform::SuperChip => Quirks
+            {
+                wrap_sprites: false,
+                reset_vf_on_logic: false,
+                display_wait: false,
+                index_increment_on_store: IndexIncrement::Unchanged,
+                jump_uses_vx: true,
+            },
+
+            Platform::XoChip => Quirks
+            {
+                wrap_sprites: true,
+                reset_vf_on_logic: false,
+                display_wait: false,
+                index_increment_on_store: IndexIncrement::ByXPlusOne,
+                jump_uses_vx: false,
+            },
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Platform>
+    {
+        let platform = match name.to_lowercase().as_str()
+        {
+            "vip" | "cosmac" | "chip8" => Platform::CosmacVip,
+            "chip48" => Platform::Chip48,
+            "schip" | "superchip" => Platform::SuperChip,
+            "xochip" => Platform::XoChip,
+            _ => return None,
+        };
+
+        Some(platform)
+    }
+
+    // Takes the ROM's file name without the extension, eg - "Space Invaders"
+    pub fn detect(rom_name: &str) -> Option<Platform>
+    {
+        KNOWN_ROMS.iter()
+                  .find(|(known, _)|
+                  {
+                      rom_name.chars()
+                              .filter(char::is_ascii_alphanumeric)
+                              .map(|c| c.to_ascii_lowercase())
+                              .eq(known.chars())
+                  })
+                  .map(|&(_, platform)| platform)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn platforms_parse_from_names()
+    {
+        assert_eq!(Platform::from_name("SCHIP"), Some(Platform::SuperChip));
+        assert_eq!(Platform::from_name("vip"), Some(Platform::CosmacVip));
+        assert_eq!(Platform::from_name("gameboy"), None);
+
+        assert!(Platform::XoChip.quirks().wrap_sprites);
+        assert!(Platform::CosmacVip.quirks().display_wait);
+        assert!(!Platform::SuperChip.quirks().reset_vf_on_logic);
+        assert!(Platform::Chip48.quirks().jump_uses_vx);
+    }
+
+    #[test]
+    fn detects_known_roms_by_name()
+    {
+        assert_eq!(Platform::detect("Space Invaders"), Some(Platform::Chip48));
+        assert_eq!(Platform::detect("BRIX"), Some(Platform::CosmacVip));
+        assert_eq!(Platform::detect("Spacefight 2091!"), Some(Platform::SuperChip));
+        assert_eq!(Platform::detect("My Homebrew"), None);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::io;
+
+use crate::machine::{Chip8, NUM_KEYS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent
+{
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+// Only changes to the keypad are logged, one event per line as
+// "<frame> <key> <pressed>", eg - "120 5 1"
+pub struct Recorder
+{
+    events: Vec<InputEvent>,
+    keypad: [bool; NUM_KEYS],
+    frame: u64,
+}
+
+impl Recorder
+{
+    pub fn new() -> Self
+    {
+        Recorder
+        {
+            events: Vec::new(),
+            keypad: [false; NUM_KEYS],
+            frame: 0,
+        }
+    }
+
+    // Called once per frame with the keypad that frame runs with
+    pub fn record(&mut self, keypad: &[bool; NUM_KEYS])
+    {
+        for (key, (&old, &new)) in self.keypad.iter().zip(keypad.iter()).enumerate()
+        {
+            if old != new
+            {
+                self.events.push(InputEvent { frame: self.frame, key: key as u8, pressed: new });
+            }
+        }
+
+        self.keypad = *keypad;
+        self.frame += 1;
+    }
+
+    pub fn events(&self) -> &[InputEvent]
+    {
+        &self.events
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()>
+    {
+        let mut text = String::new();
+        for event in &self.events
+        {
+            text.push_str(&format!("{} {:X} {}\n", event.frame, event.key, event.pressed as u8));
+        }
+
+        fs::write(path, text)
+    }
+}
+
+impl Default for Recorder
+{
+    fn default() -> Self
+    {
+        Recorder::new()
+    }
+}
+
+pub struct Playback
+{
+    events: Vec<InputEvent>,
+    next: usize,
+    frame: u64,
+}
+
+impl Playback
+{
+    pub fn new(mut events: Vec<InputEvent>) -> Self
+    {
+        events.sort_by_key(|event| event.frame);
+
+        Playback
+        {
+            events,
+            next: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self>
+    {
+        let text = fs::read_to_string(path)?;
+
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate()
+        {
+            if line.trim().is_empty()
+            {
+                continue;
+            }
+
+            let event = parse_event(line).ok_or_else(||
+                io::Error::new(io::ErrorKind::InvalidData, format!("Error Parsing Replay On Line {}", i + 1)))?;
+            events.push(event);
+        }
+
+        Ok(Playback::new(events))
+    }
+
+    // Called once per frame in place of polling the frontend
+    pub fn apply(&mut self, machine: &mut Chip8)
+    {
+        while let Some(event) = self.events.get(self.next)
+        {
+            if event.frame > self.frame
+            {
+                break;
+            }
+
+            machine.set_key(event.key as usize, event.pressed);
+            self.next += 1;
+        }
+
+        self.frame += 1;
+    }
+
+    pub fn is_finished(&self) -> bool
+    {
+        self.next == self.events.len()
+    }
+}
+
+fn parse_event(line: &str) -> Option<InputEvent>
+{
+    let mut fields = line.split_whitespace();
+
+    let frame = fields.next()?.parse().ok()?;
+    let key = u8::from_str_radix(fields.next()?, 16).ok()?;
+    let pressed = match fields.next()?
+    {
+        "0" => false,
+        "1" => true,
+        _ => return None,
+    };
+
+    if key as usize >= NUM_KEYS || fields.next().is_some()
+    {
+        return None;
+    }
+
+    Some(InputEvent { frame, key, pressed })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn playback_feeds_recorded_keys_on_the_same_frame()
+    {
+        let mut keypad = [false; NUM_KEYS];
+        let mut recorder = Recorder::new();
+
+        recorder.record(&keypad);
+        keypad[0x5] = true;
+        recorder.record(&keypad);
+        recorder.record(&keypad);
+        keypad[0x5] = false;
+        recorder.record(&keypad);
+
+        assert_eq!(recorder.events(),
+                   [InputEvent { frame: 1, key: 0x5, pressed: true },
+                    InputEvent { frame: 3, key: 0x5, pressed: false }]);
+
+        let mut machine = Chip8::new_seeded(0);
+        let mut playback = Playback::new(recorder.events().to_vec());
+
+        let held: Vec<bool> = (0..4).map(|_|
+        {
+            playback.apply(&mut machine);
+            machine.key_state(0x5)
+        }).collect();
+
+        assert_eq!(held, [false, true, true, false]);
+        assert!(playback.is_finished());
+    }
+
+    #[test]
+    fn parses_event_lines()
+    {
+        assert_eq!(parse_event("120 A 1"), Some(InputEvent { frame: 120, key: 0xA, pressed: true }));
+        assert_eq!(parse_event("120 10 1"), None);
+        assert_eq!(parse_event("120 A"), None);
+    }
+}
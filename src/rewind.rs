@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use crate::machine::{Chip8, SaveState};
+
+// Keeps a state every `interval` frames, dropping the oldest once full
+pub(crate) struct RewindBuffer
+{
+    states: VecDeque<SaveState>,
+    capacity: usize,
+    interval: u32,
+    frames: u32,
+}
+
+impl RewindBuffer
+{
+    pub(crate) fn new(capacity: usize, interval: u32) -> Self
+    {
+        RewindBuffer
+        {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+            interval: interval.max(1),
+            frames: 0,
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize)
+    {
+        self.capacity = capacity;
+        while self.states.len() > capacity
+        {
+            self.states.pop_front();
+        }
+    }
+
+    pub(crate) fn clear(&mut self)
+    {
+        self.states.clear();
+        self.frames = 0;
+    }
+
+    pub(crate) fn record(&mut self, machine: &Chip8)
+    {
+        self.frames += 1;
+        if self.frames < self.interval || self.capacity == 0
+        {
+            return;
+        }
+        self.frames = 0;
+
+        if self.states.len() == self.capacity
+        {
+            self.states.pop_front();
+        }
+        self.states.push_back(machine.save_state());
+    }
+
+    // Returns false once there is nothing older left to go back to
+    pub(crate) fn rewind(&mut self, machine: &mut Chip8) -> bool
+    {
+        match self.states.pop_back()
+        {
+            Some(state) =>
+            {
+                machine.load_state(&state);
+                self.frames = 0;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn rewinding_restores_earlier_registers()
+    {
+        // ADD V0, 1; JP 0x200
+        let mut machine = Chip8::new_seeded(0);
+        machine.load_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+
+        let mut rewind = RewindBuffer::new(3, 1);
+        for _i in 0..5
+        {
+            machine.run_for(2).unwrap();
+            rewind.record(&machine);
+        }
+        assert_eq!(machine.register(0), 5);
+
+        assert!(rewind.rewind(&mut machine));
+        assert_eq!(machine.register(0), 5);
+
+        assert!(rewind.rewind(&mut machine));
+        assert!(rewind.rewind(&mut machine));
+        assert_eq!(machine.register(0), 3);
+        assert_eq!(machine.program_counter(), 0x200);
+
+        assert!(!rewind.rewind(&mut machine));
+        assert_eq!(machine.register(0), 3);
+    }
+}
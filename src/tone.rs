@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: u32 = 44100;
+const AMPLITUDE: f32 = 0.25 * i16::MAX as f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform
+{
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform
+{
+    // One period spans phase 0 to 1
+    pub(crate) fn sample(self, phase: f32) -> f32
+    {
+        match self
+        {
+            Waveform::Square =>
+            {
+                if phase < 0.5
+                {
+                    1.0
+                }
+                else
+                {
+                    -1.0
+                }
+            },
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+// A second of the tone as a mono 16 bit WAV, meant to be looped
+// for as long as the sound timer is running
+pub fn wav(freq_hz: f32, waveform: Waveform) -> Vec<u8>
+{
+    let samples = SAMPLE_RATE;
+    let data_size = 2 * samples;
+
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(2 * SAMPLE_RATE).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    for i in 0..samples
+    {
+        let phase = (i as f32 * freq_hz / SAMPLE_RATE as f32).fract();
+        let sample = (waveform.sample(phase) * AMPLITUDE) as i16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn wav_has_a_header_and_a_second_of_samples()
+    {
+        let wav = wav(440.0, Waveform::Square);
+
+        assert_eq!(&wav[0 .. 4], b"RIFF");
+        assert_eq!(&wav[36 .. 40], b"data");
+        assert_eq!(wav.len(), 44 + 2 * SAMPLE_RATE as usize);
+    }
+
+    #[test]
+    fn waveforms_stay_within_one()
+    {
+        for &waveform in [Waveform::Square, Waveform::Sine, Waveform::Triangle].iter()
+        {
+            for i in 0..100
+            {
+                assert!(waveform.sample(i as f32 / 100.0).abs() <= 1.0);
+            }
+        }
+
+        assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+        assert_eq!(Waveform::Square.sample(0.75), -1.0);
+    }
+}
@@ -0,0 +1,71 @@
+use wasm_bindgen::prelude::*;
+
+use crate::machine::{self, Chip8};
+
+// Browsers can't read files, so ROMs are handed over as bytes and
+// the page draws video_buffer onto a canvas itself
+#[wasm_bindgen]
+pub struct Chip8Wasm
+{
+    machine: Chip8,
+}
+
+#[wasm_bindgen]
+impl Chip8Wasm
+{
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8Wasm
+    {
+        Chip8Wasm { machine: Chip8::new() }
+    }
+
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), JsValue>
+    {
+        self.machine.load_bytes(rom).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn cycle(&mut self) -> Result<bool, JsValue>
+    {
+        self.machine.cycle().map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    // Meant to be called from requestAnimationFrame at 60Hz
+    pub fn advance_frame(&mut self, cycles: u32) -> Result<bool, JsValue>
+    {
+        self.machine.advance_frame(cycles).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool)
+    {
+        self.machine.set_key(key, pressed);
+    }
+
+    // One byte per pixel, row by row, 1 for on and 0 for off
+    pub fn video_buffer(&self) -> Vec<u8>
+    {
+        self.machine.video_buffer().iter().map(|&pixel| pixel as u8).collect()
+    }
+
+    pub fn width(&self) -> usize
+    {
+        machine::VIDEO_WIDTH
+    }
+
+    pub fn height(&self) -> usize
+    {
+        machine::VIDEO_HEIGHT
+    }
+
+    pub fn is_beeping(&self) -> bool
+    {
+        self.machine.is_sound_active()
+    }
+}
+
+impl Default for Chip8Wasm
+{
+    fn default() -> Self
+    {
+        Chip8Wasm::new()
+    }
+}
@@ -0,0 +1,45 @@
+; Draws the font, a sprite clipped at the right edge and the
+; result of an 8xy4 carry so regressions in any of them change the screen
+        CLS
+        LD V0, 0
+        LD V1, 1
+        LD V2, 1
+digits:
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V0, 1
+        ADD V1, 5
+        SE V0, 12
+        JP digits
+
+        LD V1, 60
+        LD V2, 10
+        LD I, box
+        DRW V1, V2, 4
+
+        LD V4, 0xC8
+        LD V5, 0x64
+        ADD V4, V5
+        LD V6, VF
+        LD I, scratch
+        LD B, V4
+        LD V2, [I]
+        LD V3, 20
+        LD V7, 1
+        LD F, V6
+        DRW V7, V3, 5
+        LD V7, 11
+        LD F, V0
+        DRW V7, V3, 5
+        LD V7, 16
+        LD F, V1
+        DRW V7, V3, 5
+        LD V7, 21
+        LD F, V2
+        DRW V7, V3, 5
+end:
+        JP end
+box:
+        DB 0xFF, 0x81, 0x81, 0xFF
+scratch:
+        DB 0, 0, 0
@@ -0,0 +1,34 @@
+use sha2::{Digest, Sha256};
+
+use rusty_chip::{asm, Chip8};
+
+// Assembled from roms/screen.asm
+const SCREEN_ROM: &[u8] = include_bytes!("roms/screen.ch8");
+const SCREEN_SOURCE: &str = include_str!("roms/screen.asm");
+
+// The ROM ends in an infinite loop so any number of cycles past
+// its last draw gives the same screen
+const SCREEN_CYCLES: usize = 1000;
+const SCREEN_SHA256: &str = "683f53b3d07c8eca620fcca4b1bebd6a51f39a79973ea6ec8d927bd274a26b97";
+
+fn screen_hash(machine: &Chip8) -> String
+{
+    let pixels: Vec<u8> = machine.video_buffer().iter().map(|&pixel| pixel as u8).collect();
+    format!("{:x}", Sha256::digest(&pixels))
+}
+
+#[test]
+fn screen_rom_matches_golden_hash()
+{
+    let mut machine = Chip8::new_seeded(0);
+    machine.load_bytes(SCREEN_ROM).unwrap();
+    machine.run_for(SCREEN_CYCLES).unwrap();
+
+    assert_eq!(screen_hash(&machine), SCREEN_SHA256, "\n{}", machine.screen_ascii());
+}
+
+#[test]
+fn screen_rom_matches_its_source()
+{
+    assert_eq!(asm::assemble(SCREEN_SOURCE).unwrap(), SCREEN_ROM);
+}